@@ -0,0 +1,183 @@
+use crate::DecodeIdError;
+
+/// The Binary trait is a compact encoding of identifiers, so they can
+/// cross process boundaries. The integers are encoded as variable length
+/// integers (LEB128), the signed ones after a zigzag mapping, so small
+/// values take a single byte. The strings are prefixed with their length.
+pub trait Binary: Sized {
+    /// Appends the encoding of the value to the buffer.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Decodes a value from the front of the buffer, advancing it past
+    /// the bytes read.
+    fn decode(buf: &mut &[u8]) -> Result<Self, DecodeIdError>;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode(&mut buf);
+        buf
+    }
+
+    /// Decodes a value which must span all the bytes.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeIdError> {
+        let mut buf = bytes;
+        let value = Self::decode(&mut buf)?;
+        if !buf.is_empty() {
+            return Err(DecodeIdError::TrailingBytes(buf.len()));
+        }
+        Ok(value)
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u128) {
+    while value >= 0x80 {
+        buf.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn read_varint(buf: &mut &[u8]) -> Result<u128, DecodeIdError> {
+    let mut value = 0_u128;
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = buf.split_first().ok_or(DecodeIdError::UnexpectedEnd)?;
+        *buf = rest;
+
+        let bits = (byte & 0x7f) as u128;
+        if shift >= 128 || (shift > 0 && bits >> (128 - shift) != 0) {
+            return Err(DecodeIdError::Overflow);
+        }
+        value |= bits << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+macro_rules! impl_binary_unsigned {
+    ($($t:ty),*) => {
+        $(
+            impl Binary for $t {
+                fn encode(&self, buf: &mut Vec<u8>) {
+                    write_varint(buf, *self as u128);
+                }
+
+                fn decode(buf: &mut &[u8]) -> Result<Self, DecodeIdError> {
+                    <$t>::try_from(read_varint(buf)?).map_err(|_| DecodeIdError::Overflow)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_binary_signed {
+    ($($t:ty),*) => {
+        $(
+            impl Binary for $t {
+                fn encode(&self, buf: &mut Vec<u8>) {
+                    let value = *self as i128;
+                    write_varint(buf, ((value << 1) ^ (value >> 127)) as u128);
+                }
+
+                fn decode(buf: &mut &[u8]) -> Result<Self, DecodeIdError> {
+                    let zigzag = read_varint(buf)?;
+                    let value = (zigzag >> 1) as i128 ^ -((zigzag & 1) as i128);
+                    <$t>::try_from(value).map_err(|_| DecodeIdError::Overflow)
+                }
+            }
+        )*
+    };
+}
+
+impl_binary_unsigned!(u8, u16, u32, u64, u128, usize);
+impl_binary_signed!(i8, i16, i32, i64, i128, isize);
+
+impl Binary for bool {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(*self as u8);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self, DecodeIdError> {
+        let (&byte, rest) = buf.split_first().ok_or(DecodeIdError::UnexpectedEnd)?;
+        *buf = rest;
+        match byte {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => Err(DecodeIdError::InvalidBool(b)),
+        }
+    }
+}
+
+impl Binary for String {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.len().encode(buf);
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self, DecodeIdError> {
+        let len = usize::decode(buf)?;
+        if buf.len() < len {
+            return Err(DecodeIdError::UnexpectedEnd);
+        }
+        let (bytes, rest) = buf.split_at(len);
+        *buf = rest;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeIdError::InvalidUtf8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varint_sizes() {
+        assert_eq!(0_u64.to_bytes(), vec![0]);
+        assert_eq!(127_u64.to_bytes(), vec![0x7f]);
+        assert_eq!(128_u64.to_bytes(), vec![0x80, 0x01]);
+        assert_eq!(u64::MAX.to_bytes().len(), 10);
+        assert_eq!((-1_i32).to_bytes(), vec![0x01]);
+        assert_eq!(1_i32.to_bytes(), vec![0x02]);
+    }
+
+    #[test]
+    fn round_trip() {
+        for value in [0_u64, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            assert_eq!(u64::from_bytes(&value.to_bytes()), Ok(value));
+        }
+        for value in [0_i64, -1, 1, -64, 64, i64::MIN, i64::MAX] {
+            assert_eq!(i64::from_bytes(&value.to_bytes()), Ok(value));
+        }
+        assert_eq!(u128::from_bytes(&u128::MAX.to_bytes()), Ok(u128::MAX));
+        assert_eq!(i128::from_bytes(&i128::MIN.to_bytes()), Ok(i128::MIN));
+        assert_eq!(bool::from_bytes(&true.to_bytes()), Ok(true));
+
+        let s = "worker-3".to_string();
+        assert_eq!(String::from_bytes(&s.to_bytes()), Ok(s));
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_eq!(
+            u8::from_bytes(&300_u32.to_bytes()),
+            Err(DecodeIdError::Overflow)
+        );
+        assert_eq!(u32::from_bytes(&[0x80]), Err(DecodeIdError::UnexpectedEnd));
+        assert_eq!(u128::from_bytes(&[0xff; 20]), Err(DecodeIdError::Overflow));
+        assert_eq!(
+            u8::from_bytes(&[1, 2]),
+            Err(DecodeIdError::TrailingBytes(1))
+        );
+        assert_eq!(bool::from_bytes(&[2]), Err(DecodeIdError::InvalidBool(2)));
+        assert_eq!(
+            String::from_bytes(&[5, b'a']),
+            Err(DecodeIdError::UnexpectedEnd)
+        );
+        assert_eq!(
+            String::from_bytes(&[1, 0xff]),
+            Err(DecodeIdError::InvalidUtf8)
+        );
+    }
+}
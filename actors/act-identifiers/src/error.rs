@@ -0,0 +1,56 @@
+use std::fmt::Display;
+
+/// The error returned when an identifier cannot be parsed from its
+/// `Display` format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseIdError {
+    /// The text does not have the shape of the identifier, like a message
+    /// identifier without its parentheses or its `:` separator.
+    Format(String),
+    /// The actor identifier could not be parsed.
+    Aid(String),
+    /// The message identifier part could not be parsed.
+    Mid(String),
+}
+
+impl Display for ParseIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseIdError::Format(s) => write!(f, "invalid identifier format [{s}]"),
+            ParseIdError::Aid(e) => write!(f, "invalid actor identifier [{e}]"),
+            ParseIdError::Mid(e) => write!(f, "invalid message identifier [{e}]"),
+        }
+    }
+}
+
+impl std::error::Error for ParseIdError {}
+
+/// The error returned when an identifier cannot be decoded from its
+/// binary encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeIdError {
+    /// The buffer ended in the middle of the value.
+    UnexpectedEnd,
+    /// A variable length integer does not fit its type.
+    Overflow,
+    /// A string is not valid UTF-8.
+    InvalidUtf8,
+    /// A boolean is neither 0 nor 1.
+    InvalidBool(u8),
+    /// Bytes are left after the value, see [`crate::Binary::from_bytes`].
+    TrailingBytes(usize),
+}
+
+impl Display for DecodeIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeIdError::UnexpectedEnd => write!(f, "unexpected end of the buffer"),
+            DecodeIdError::Overflow => write!(f, "the integer overflows its type"),
+            DecodeIdError::InvalidUtf8 => write!(f, "the string is not valid UTF-8"),
+            DecodeIdError::InvalidBool(b) => write!(f, "invalid boolean [{b}]"),
+            DecodeIdError::TrailingBytes(n) => write!(f, "{n} trailing bytes after the value"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeIdError {}
@@ -1,11 +1,17 @@
 use act_ids::Identifier;
 
+mod binary;
+mod error;
 mod from_id;
 mod msg_id;
+mod msg_id_generator;
 mod to_id;
 
+pub use binary::*;
+pub use error::*;
 pub use from_id::*;
 pub use msg_id::*;
+pub use msg_id_generator::*;
 pub use to_id::*;
 
 pub trait ActorId: Identifier {}
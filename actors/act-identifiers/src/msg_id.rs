@@ -1,7 +1,13 @@
-use crate::ActorId;
+use crate::{ActorId, Binary, DecodeIdError, ParseIdError};
 use act_ids::Identifier;
-use std::fmt::{Debug, Display};
+use std::{
+    cmp::Ordering,
+    fmt::{Debug, Display},
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgId<A, I>
 where
     A: ActorId,
@@ -32,6 +38,18 @@ where
     }
 }
 
+impl<A, I> MsgId<A, I>
+where
+    A: ActorId,
+    I: Identifier,
+{
+    /// Consumes the identifier returning the actor identifier and the
+    /// message identifier part.
+    pub fn into_parts(self) -> (A, I) {
+        (self.aid, self.mid)
+    }
+}
+
 impl<A, I> Clone for MsgId<A, I>
 where
     A: ActorId + Clone,
@@ -45,6 +63,20 @@ where
     }
 }
 
+//
+// From
+//
+
+impl<A, I> From<(A, I)> for MsgId<A, I>
+where
+    A: ActorId,
+    I: Identifier,
+{
+    fn from((aid, mid): (A, I)) -> Self {
+        Self { aid, mid }
+    }
+}
+
 //
 // Debug + Display
 //
@@ -69,6 +101,60 @@ where
     }
 }
 
+//
+// FromStr
+//
+
+/// Parses the `Display` format `(aid:mid)`. The actor identifier ends at
+/// the last `:`, so it may contain the separator but the message
+/// identifier part may not.
+impl<A, I> FromStr for MsgId<A, I>
+where
+    A: ActorId + FromStr,
+    A::Err: Display,
+    I: Identifier + FromStr,
+    I::Err: Display,
+{
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (aid, mid) = s
+            .strip_prefix('(')
+            .and_then(|s| s.strip_suffix(')'))
+            .and_then(|s| s.rsplit_once(':'))
+            .ok_or_else(|| ParseIdError::Format(s.to_string()))?;
+        let aid = aid
+            .parse::<A>()
+            .map_err(|e| ParseIdError::Aid(e.to_string()))?;
+        let mid = mid
+            .parse::<I>()
+            .map_err(|e| ParseIdError::Mid(e.to_string()))?;
+        Ok(Self { aid, mid })
+    }
+}
+
+//
+// Binary
+//
+
+/// Encodes the actor identifier followed by the message identifier part.
+impl<A, I> Binary for MsgId<A, I>
+where
+    A: ActorId + Binary,
+    I: Identifier + Binary,
+{
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.aid.encode(buf);
+        self.mid.encode(buf);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self, DecodeIdError> {
+        let aid = A::decode(buf)?;
+        let mid = I::decode(buf)?;
+        Ok(Self { aid, mid })
+    }
+}
+
 //
 // PartialEq, Eq, PartialOrd, Ord
 //
@@ -90,9 +176,52 @@ where
 {
 }
 
+/// The identifiers are ordered by actor, then by their message identifier
+/// part, so the identifiers of one actor follow its sequence.
+impl<A, I> PartialOrd for MsgId<A, I>
+where
+    A: ActorId + PartialOrd,
+    I: Identifier + PartialOrd,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.aid.partial_cmp(&other.aid)? {
+            Ordering::Equal => self.mid.partial_cmp(&other.mid),
+            ordering => Some(ordering),
+        }
+    }
+}
+
+impl<A, I> Ord for MsgId<A, I>
+where
+    A: ActorId + Ord,
+    I: Identifier + Ord,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.aid
+            .cmp(&other.aid)
+            .then_with(|| self.mid.cmp(&other.mid))
+    }
+}
+
+//
+// Hash
+//
+
+impl<A, I> Hash for MsgId<A, I>
+where
+    A: ActorId + Hash,
+    I: Identifier + Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.aid.hash(state);
+        self.mid.hash(state);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use act_ids::IntoIdentifierIterator;
+    use std::collections::HashSet;
 
     use super::*;
 
@@ -123,6 +252,91 @@ mod tests {
         assert_eq!(mid1, mid2)
     }
 
+    #[test]
+    fn from_parts() {
+        let mid = MsgId::from(("A", 7_u32));
+        assert_eq!(mid.aid(), &"A");
+        assert_eq!(mid.mid(), &7);
+    }
+
+    #[test]
+    fn ord() {
+        let mut mids = vec![
+            MsgId::from(("B", 1_u64)),
+            MsgId::from(("A", 2)),
+            MsgId::from(("A", 10)),
+            MsgId::from(("A", 1)),
+        ];
+        mids.sort();
+        assert_eq!(
+            mids,
+            vec![
+                MsgId::from(("A", 1)),
+                MsgId::from(("A", 2)),
+                MsgId::from(("A", 10)),
+                MsgId::from(("B", 1)),
+            ]
+        );
+        assert!(MsgId::from(("A", 9_u64)) < MsgId::from(("B", 1)));
+    }
+
+    #[test]
+    fn hash() {
+        let mut seen = HashSet::new();
+        assert!(seen.insert(MsgId::from(("A", 1_u64))));
+        assert!(seen.insert(MsgId::from(("B", 1))));
+        assert!(!seen.insert(MsgId::from(("A", 1))));
+        assert_eq!(seen.len(), 2);
+    }
+
+    #[test]
+    fn from_str() {
+        let mid = "(A:7)".parse::<MsgId<String, u64>>().unwrap();
+        assert_eq!(mid, MsgId::from(("A".to_string(), 7)));
+        assert_eq!(mid.to_string().parse::<MsgId<String, u64>>(), Ok(mid));
+
+        // the actor identifier may contain the separator.
+        let mid = "(node-1:A:7)".parse::<MsgId<String, u64>>().unwrap();
+        assert_eq!(mid.aid(), "node-1:A");
+        assert_eq!(mid.mid(), &7);
+
+        for invalid in ["A:7", "(A:7", "(A7)"] {
+            assert!(matches!(
+                invalid.parse::<MsgId<String, u64>>(),
+                Err(ParseIdError::Format(_))
+            ));
+        }
+        assert!(matches!(
+            "(x:7)".parse::<MsgId<u32, u64>>(),
+            Err(ParseIdError::Aid(_))
+        ));
+        assert!(matches!(
+            "(1:x)".parse::<MsgId<u32, u64>>(),
+            Err(ParseIdError::Mid(_))
+        ));
+    }
+
+    #[test]
+    fn binary() {
+        let mid = MsgId::from((10_u32, 300_u64));
+        let bytes = mid.to_bytes();
+        assert_eq!(bytes, vec![10, 0xac, 0x02]);
+        assert_eq!(MsgId::<u32, u64>::from_bytes(&bytes), Ok(mid));
+        assert_eq!(
+            MsgId::<u32, u64>::from_bytes(&[10]),
+            Err(DecodeIdError::UnexpectedEnd)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let mid = MsgId::from((10_u32, 7_u64));
+        let json = serde_json::to_string(&mid).unwrap();
+        assert_eq!(json, r#"{"aid":10,"mid":7}"#);
+        assert_eq!(serde_json::from_str::<MsgId<u32, u64>>(&json).unwrap(), mid);
+    }
+
     #[test]
     fn iter_ids_u32() {
         let mid = MsgId::<_, u32>::new("A");
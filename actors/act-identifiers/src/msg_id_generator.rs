@@ -0,0 +1,120 @@
+use crate::{ActorId, MsgId};
+use act_ids::{Identifier, IntoIdentifierIterator};
+use std::{fmt::Debug, ops::Add, sync::Mutex};
+
+type MsgIdIterator<A, I> = Box<dyn Iterator<Item = MsgId<A, I>> + Send>;
+
+/// The MsgIdGenerator hands out the message identifiers of one actor, in
+/// sequence. It can be shared between threads, each identifier is handed
+/// out once.
+pub struct MsgIdGenerator<A, I>
+where
+    A: ActorId,
+    I: Identifier,
+{
+    aid: A,
+    ids: Mutex<MsgIdIterator<A, I>>,
+}
+
+impl<A, I> MsgIdGenerator<A, I>
+where
+    A: ActorId + Clone + Send + 'static,
+    I: Identifier + Clone + Default + Send + 'static,
+{
+    /// Creates a generator whose first identifier has the default message
+    /// identifier part, each following one computed by `next` from the
+    /// previous one.
+    pub fn new<F>(aid: A, next: F) -> Self
+    where
+        F: Fn(I) -> I + Send + 'static,
+    {
+        let ids = MsgId::new(aid.clone()).into_ids_iterator(move |mid: MsgId<A, I>| {
+            let (aid, mid) = mid.into_parts();
+            MsgId::from((aid, next(mid)))
+        });
+        Self {
+            aid,
+            ids: Mutex::new(Box::new(ids)),
+        }
+    }
+
+    /// Creates a generator counting from zero.
+    pub fn counter(aid: A) -> Self
+    where
+        I: Add<Output = I> + From<u8>,
+    {
+        Self::new(aid, |mid: I| mid + I::from(1))
+    }
+
+    pub fn aid(&self) -> &A {
+        &self.aid
+    }
+
+    /// Hands out the next identifier of the sequence.
+    pub fn next_id(&self) -> MsgId<A, I> {
+        let mut ids = self.ids.lock().unwrap_or_else(|e| e.into_inner());
+        ids.next().expect("the sequence of identifiers is infinite")
+    }
+}
+
+impl<A, I> Debug for MsgIdGenerator<A, I>
+where
+    A: ActorId + Debug,
+    I: Identifier,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MsgIdGenerator")
+            .field("aid", &self.aid)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::HashSet, sync::Arc, thread};
+
+    #[test]
+    fn counter() {
+        let generator = MsgIdGenerator::<_, u64>::counter("A");
+        assert_eq!(generator.aid(), &"A");
+        assert_eq!(generator.next_id(), MsgId::from(("A", 0)));
+        assert_eq!(generator.next_id(), MsgId::from(("A", 1)));
+        assert_eq!(generator.next_id(), MsgId::from(("A", 2)));
+    }
+
+    #[test]
+    fn custom_step() {
+        let generator = MsgIdGenerator::<_, u32>::new(7_u8, |mid| mid + 10);
+        let mids = (0..3)
+            .map(|_| *generator.next_id().mid())
+            .collect::<Vec<_>>();
+        assert_eq!(mids, vec![0, 10, 20]);
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let generator = Arc::new(MsgIdGenerator::<_, u64>::counter(1_u32));
+
+        let handles = (0..4)
+            .map(|_| {
+                let generator = generator.clone();
+                thread::spawn(move || {
+                    let ids = (0..100).map(|_| generator.next_id()).collect::<Vec<_>>();
+                    // each thread sees its identifiers in order.
+                    assert!(ids.windows(2).all(|w| w[0] < w[1]));
+                    ids
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut seen = HashSet::new();
+        for handle in handles {
+            for id in handle.join().unwrap() {
+                assert!(seen.insert(id));
+            }
+        }
+        assert_eq!(seen.len(), 400);
+        assert_eq!(generator.next_id(), MsgId::from((1, 400)));
+    }
+}
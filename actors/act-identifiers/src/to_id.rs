@@ -1,6 +1,14 @@
-use crate::{ActorId, FromId};
-use std::fmt::{Debug, Display};
-
+use crate::{ActorId, Binary, DecodeIdError, FromId, ParseIdError};
+use std::{
+    fmt::{Debug, Display},
+    str::FromStr,
+};
+
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct ToId<A: ActorId>(pub(crate) A);
 
 impl<A> ToId<A>
@@ -56,6 +64,42 @@ where
     }
 }
 
+//
+// FromStr
+//
+
+/// Parses the `Display` format, which is the one of the actor identifier.
+impl<A> FromStr for ToId<A>
+where
+    A: ActorId + FromStr,
+    A::Err: Display,
+{
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<A>()
+            .map(Self)
+            .map_err(|e| ParseIdError::Aid(e.to_string()))
+    }
+}
+
+//
+// Binary
+//
+
+impl<A> Binary for ToId<A>
+where
+    A: ActorId + Binary,
+{
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.0.encode(buf);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self, DecodeIdError> {
+        A::decode(buf).map(Self)
+    }
+}
+
 //
 // PartialEq, Eq, PartialOrd, Ord
 //
@@ -145,6 +189,34 @@ mod tests {
         assert_eq!(tid.0, "fid");
     }
 
+    #[test]
+    fn from_str() {
+        let id = "10".parse::<ToId<u32>>().unwrap();
+        assert_eq!(id, ToId::from(10));
+        assert_eq!(id.to_string().parse::<ToId<u32>>(), Ok(id));
+        assert!(matches!(
+            "x".parse::<ToId<u32>>(),
+            Err(ParseIdError::Aid(_))
+        ));
+    }
+
+    #[test]
+    fn binary() {
+        let id = ToId::from("worker-3".to_string());
+        let bytes = id.to_bytes();
+        assert_eq!(bytes.len(), 9);
+        assert_eq!(ToId::<String>::from_bytes(&bytes), Ok(id));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let id = ToId::from(10_u32);
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, "10");
+        assert_eq!(serde_json::from_str::<ToId<u32>>(&json).unwrap(), id);
+    }
+
     #[test]
     fn iter_ids_u32() {
         let id = ToId::from(10_u32);
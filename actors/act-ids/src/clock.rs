@@ -0,0 +1,272 @@
+use std::{cmp::Ordering, collections::BTreeMap, fmt::Display};
+
+/// A Lamport clock: a counter which every process increments on each of
+/// its events and which jumps past the clock of every message it receives.
+/// If an event happened before another one, its timestamp is lower; the
+/// converse does not hold, two concurrent events are ordered arbitrarily.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LamportClock(u64);
+
+impl LamportClock {
+    pub fn new() -> Self {
+        Self(0)
+    }
+
+    pub fn time(&self) -> u64 {
+        self.0
+    }
+
+    /// Records a local event, like sending a message, and returns its timestamp.
+    pub fn tick(&mut self) -> LamportClock {
+        self.0 += 1;
+        *self
+    }
+
+    /// Records the receipt of a message carrying the given timestamp and
+    /// returns the timestamp of the receipt, later than both clocks.
+    pub fn merge(&mut self, other: &LamportClock) -> LamportClock {
+        self.0 = self.0.max(other.0) + 1;
+        *self
+    }
+}
+
+impl From<u64> for LamportClock {
+    fn from(time: u64) -> Self {
+        Self(time)
+    }
+}
+
+impl Display for LamportClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A vector clock: one counter per node, so it captures causality exactly.
+/// A clock is lower than another if the event it stamps happened before
+/// the other one; the clocks of concurrent events are incomparable, their
+/// `partial_cmp` is `None`.
+///
+/// The counters at zero are not stored, a missing node counts as zero.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VectorClock<N>
+where
+    N: Ord,
+{
+    counters: BTreeMap<N, u64>,
+}
+
+impl<N> Default for VectorClock<N>
+where
+    N: Ord,
+{
+    fn default() -> Self {
+        Self {
+            counters: BTreeMap::new(),
+        }
+    }
+}
+
+impl<N> VectorClock<N>
+where
+    N: Ord + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The counter of the node.
+    pub fn get(&self, node: &N) -> u64 {
+        self.counters.get(node).copied().unwrap_or(0)
+    }
+
+    /// Records an event of the node, like sending a message.
+    pub fn tick(&mut self, node: &N) -> &mut Self {
+        *self.counters.entry(node.clone()).or_insert(0) += 1;
+        self
+    }
+
+    /// Takes, for every node, the larger of the two counters. On the
+    /// receipt of a message, merge the clock it carries and then tick
+    /// the receiving node.
+    pub fn merge(&mut self, other: &VectorClock<N>) -> &mut Self {
+        for (node, counter) in &other.counters {
+            let entry = self.counters.entry(node.clone()).or_insert(0);
+            *entry = (*entry).max(*counter);
+        }
+        self
+    }
+
+    /// Returns `true` if the event this clock stamps happened before the other's.
+    pub fn happened_before(&self, other: &VectorClock<N>) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Less)
+    }
+
+    /// Returns `true` if neither event happened before the other.
+    pub fn is_concurrent(&self, other: &VectorClock<N>) -> bool {
+        self.partial_cmp(other).is_none()
+    }
+
+    /// The nodes with a counter above zero and their counters, ordered by node.
+    pub fn iter(&self) -> impl Iterator<Item = (&N, u64)> {
+        self.counters.iter().map(|(node, counter)| (node, *counter))
+    }
+}
+
+impl<N> PartialOrd for VectorClock<N>
+where
+    N: Ord,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let (mut less, mut greater) = (false, false);
+
+        let mut lhs = self.counters.iter().peekable();
+        let mut rhs = other.counters.iter().peekable();
+        loop {
+            // Walk both maps in node order, a missing node counting as zero.
+            let ordering = match (lhs.peek(), rhs.peek()) {
+                (None, None) => break,
+                (Some(_), None) => {
+                    lhs.next();
+                    Ordering::Greater
+                }
+                (None, Some(_)) => {
+                    rhs.next();
+                    Ordering::Less
+                }
+                (Some((lnode, lcounter)), Some((rnode, rcounter))) => match lnode.cmp(rnode) {
+                    Ordering::Less => {
+                        lhs.next();
+                        Ordering::Greater
+                    }
+                    Ordering::Greater => {
+                        rhs.next();
+                        Ordering::Less
+                    }
+                    Ordering::Equal => {
+                        let ordering = lcounter.cmp(rcounter);
+                        lhs.next();
+                        rhs.next();
+                        ordering
+                    }
+                },
+            };
+            match ordering {
+                Ordering::Less => less = true,
+                Ordering::Greater => greater = true,
+                Ordering::Equal => (),
+            }
+            if less && greater {
+                return None;
+            }
+        }
+
+        match (less, greater) {
+            (true, _) => Some(Ordering::Less),
+            (_, true) => Some(Ordering::Greater),
+            _ => Some(Ordering::Equal),
+        }
+    }
+}
+
+impl<N> Display for VectorClock<N>
+where
+    N: Ord + Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{{")?;
+        for (i, (node, counter)) in self.counters.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{node}: {counter}")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PartialOrdIdentifier;
+
+    fn cmp_identifiers<I>(a: &I, b: &I) -> Option<Ordering>
+    where
+        I: PartialOrdIdentifier,
+    {
+        a.partial_cmp(b)
+    }
+
+    #[test]
+    fn lamport() {
+        let mut a = LamportClock::new();
+        let mut b = LamportClock::from(5);
+
+        let sent = a.tick();
+        assert_eq!(sent.time(), 1);
+        let received = b.merge(&sent);
+        assert_eq!(received.time(), 6);
+        assert_eq!(cmp_identifiers(&sent, &received), Some(Ordering::Less));
+
+        // the receiver is behind the sender.
+        let sent = b.tick();
+        let received = a.merge(&sent);
+        assert_eq!(received.time(), 8);
+        assert_eq!(received.to_string(), "8");
+    }
+
+    #[test]
+    fn vector_happened_before() {
+        let mut a = VectorClock::new();
+        let mut b = VectorClock::new();
+
+        a.tick(&"a");
+        let sent = a.clone();
+        b.merge(&sent).tick(&"b");
+
+        assert!(sent.happened_before(&b));
+        assert_eq!(cmp_identifiers(&sent, &b), Some(Ordering::Less));
+        assert_eq!(cmp_identifiers(&b, &sent), Some(Ordering::Greater));
+        assert_eq!(b.get(&"a"), 1);
+        assert_eq!(b.get(&"c"), 0);
+        assert_eq!(b.to_string(), "{a: 1, b: 1}");
+    }
+
+    #[test]
+    fn vector_concurrent() {
+        let mut a = VectorClock::new();
+        let mut b = VectorClock::new();
+        a.tick(&1_u32);
+        b.tick(&2_u32);
+
+        assert!(a.is_concurrent(&b));
+        assert_eq!(cmp_identifiers(&a, &b), None);
+        assert!(b.is_concurrent(&a) && a != b);
+
+        // merging both makes a clock after both.
+        let mut c = a.clone();
+        c.merge(&b);
+        assert!(a < c && b < c);
+        assert_eq!(c.iter().collect::<Vec<_>>(), vec![(&1, 1), (&2, 1)]);
+    }
+
+    #[test]
+    fn vector_equal() {
+        let mut a = VectorClock::new();
+        let mut b = VectorClock::new();
+        a.tick(&"x").tick(&"y");
+        b.tick(&"y").tick(&"x");
+        assert_eq!(a, b);
+        assert_eq!(a.partial_cmp(&b), Some(Ordering::Equal));
+        assert_eq!(
+            VectorClock::<u8>::new().partial_cmp(&VectorClock::new()),
+            Some(Ordering::Equal)
+        );
+
+        // a node missing on one side counts as zero.
+        let mut c = a.clone();
+        c.tick(&"z");
+        assert!(a < c);
+        assert!(VectorClock::new() < a);
+    }
+}
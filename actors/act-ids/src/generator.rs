@@ -0,0 +1,112 @@
+use crate::Identifier;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The IdGenerator trait represents a source of unique identifiers. The
+/// generators can be shared between threads: every call hands out a new
+/// identifier, even when the calls are concurrent.
+pub trait IdGenerator: Send + Sync {
+    type Id: Identifier;
+
+    fn next_id(&self) -> Self::Id;
+
+    /// An endless iterator over the identifiers of the generator.
+    fn ids(&self) -> impl Iterator<Item = Self::Id> + '_ {
+        std::iter::repeat_with(move || self.next_id())
+    }
+}
+
+/// Hands out increasing numbers, starting from a given one. The numbers
+/// are unique within the generator only.
+#[derive(Debug, Default)]
+pub struct CounterGenerator {
+    next: AtomicU64,
+}
+
+impl CounterGenerator {
+    pub fn new() -> Self {
+        Self::starting_at(0)
+    }
+
+    pub fn starting_at(first: u64) -> Self {
+        Self {
+            next: AtomicU64::new(first),
+        }
+    }
+}
+
+impl IdGenerator for CounterGenerator {
+    type Id = u64;
+
+    /// Panics once the counter reaches `u64::MAX`, and on every call after
+    /// that: wrapping around would repeat identifiers.
+    fn next_id(&self) -> u64 {
+        self.next
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |id| id.checked_add(1))
+            .expect("the counter generator is exhausted")
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::{collections::HashSet, sync::Arc, thread};
+
+    /// Draws ids from the generator on several threads and checks that
+    /// they are unique and that each thread sees them increasing.
+    pub(crate) fn check_concurrent<G>(generator: G)
+    where
+        G: IdGenerator + 'static,
+        G::Id: Ord + std::hash::Hash + Send + 'static,
+    {
+        let generator = Arc::new(generator);
+        let handles = (0..4)
+            .map(|_| {
+                let generator = generator.clone();
+                thread::spawn(move || generator.ids().take(1000).collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+
+        let mut seen = HashSet::new();
+        for handle in handles {
+            let ids = handle.join().unwrap();
+            assert!(ids.windows(2).all(|w| w[0] < w[1]));
+            for id in ids {
+                assert!(seen.insert(id));
+            }
+        }
+        assert_eq!(seen.len(), 4000);
+    }
+
+    #[test]
+    fn counter() {
+        let generator = CounterGenerator::starting_at(10);
+        assert_eq!(
+            generator.ids().take(3).collect::<Vec<_>>(),
+            vec![10, 11, 12]
+        );
+        assert_eq!(generator.next_id(), 13);
+    }
+
+    #[test]
+    fn counter_concurrent() {
+        check_concurrent(CounterGenerator::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "exhausted")]
+    fn counter_exhausted() {
+        let generator = CounterGenerator::starting_at(u64::MAX - 1);
+        generator.next_id();
+        generator.next_id();
+    }
+
+    #[test]
+    fn counter_stays_exhausted() {
+        let generator = CounterGenerator::starting_at(u64::MAX - 1);
+        assert_eq!(generator.next_id(), u64::MAX - 1);
+        for _ in 0..2 {
+            let next = std::panic::catch_unwind(|| generator.next_id());
+            assert!(next.is_err());
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use crate::IdGenerator;
+use std::{
+    fmt::Display,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The default bound on how far ahead of the local clock a remote
+/// timestamp may be.
+const DEFAULT_MAX_DRIFT: Duration = Duration::from_secs(60);
+
+/// A hybrid logical clock timestamp: the milliseconds since the Unix epoch
+/// and a logical counter which orders the events of the same millisecond.
+/// The timestamps are totally ordered, by time and then by counter, and
+/// they stay close to the wall clock time.
+///
+/// They display as `<millis>.<counter>`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HlcTimestamp {
+    physical: u64,
+    logical: u32,
+}
+
+impl HlcTimestamp {
+    pub fn new(physical: u64, logical: u32) -> Self {
+        Self { physical, logical }
+    }
+
+    /// The milliseconds since the Unix epoch.
+    pub fn physical(&self) -> u64 {
+        self.physical
+    }
+
+    pub fn logical(&self) -> u32 {
+        self.logical
+    }
+
+    /// The smallest timestamp after this one, so a timestamp can seed an
+    /// [`IntoIdentifierIterator`](crate::IntoIdentifierIterator):
+    /// `ts.into_ids_iterator(HlcTimestamp::successor)`.
+    pub fn successor(self) -> Self {
+        match self.logical.checked_add(1) {
+            Some(logical) => Self::new(self.physical, logical),
+            None => Self::new(self.physical + 1, 0),
+        }
+    }
+}
+
+impl Display for HlcTimestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.physical, self.logical)
+    }
+}
+
+impl FromStr for HlcTimestamp {
+    type Err = ParseHlcTimestampError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (physical, logical) = s
+            .split_once('.')
+            .ok_or_else(|| ParseHlcTimestampError(s.to_string()))?;
+        match (physical.parse(), logical.parse()) {
+            (Ok(physical), Ok(logical)) => Ok(Self::new(physical, logical)),
+            _ => Err(ParseHlcTimestampError(s.to_string())),
+        }
+    }
+}
+
+/// The string is not a `<millis>.<counter>` timestamp.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseHlcTimestampError(String);
+
+impl Display for ParseHlcTimestampError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid hybrid logical clock timestamp [{}]", self.0)
+    }
+}
+
+impl std::error::Error for ParseHlcTimestampError {}
+
+/// A remote timestamp is further ahead of the local clock than the
+/// maximum drift allows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClockDriftError {
+    pub remote: HlcTimestamp,
+    pub local: u64,
+    pub max_drift: Duration,
+}
+
+impl Display for ClockDriftError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The remote timestamp [{}] is more than {:?} ahead of the local clock [{}]",
+            self.remote, self.max_drift, self.local
+        )
+    }
+}
+
+impl std::error::Error for ClockDriftError {}
+
+/// A hybrid logical clock (Kulkarni et al.): it stamps the local events
+/// and the messages sent with [`now`](Self::now), and merges the
+/// timestamps of the messages received with [`update`](Self::update). An
+/// event which happened before another one has a lower timestamp, without
+/// any central sequencer.
+///
+/// A remote timestamp more than the maximum drift ahead of the local
+/// clock is rejected, so a node with a skewed clock cannot drag the
+/// others into the future.
+#[derive(Debug)]
+pub struct HybridLogicalClock {
+    max_drift: Duration,
+    last: Mutex<HlcTimestamp>,
+}
+
+impl Default for HybridLogicalClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HybridLogicalClock {
+    pub fn new() -> Self {
+        Self {
+            max_drift: DEFAULT_MAX_DRIFT,
+            last: Mutex::new(HlcTimestamp::default()),
+        }
+    }
+
+    /// Bounds how far ahead of the local clock a remote timestamp may be,
+    /// one minute by default.
+    pub fn with_max_drift(mut self, max_drift: Duration) -> Self {
+        self.max_drift = max_drift;
+        self
+    }
+
+    pub fn max_drift(&self) -> Duration {
+        self.max_drift
+    }
+
+    /// The last timestamp handed out.
+    pub fn last(&self) -> HlcTimestamp {
+        *self.last.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Stamps a local event, like sending a message.
+    pub fn now(&self) -> HlcTimestamp {
+        self.now_at(wall_clock())
+    }
+
+    /// Stamps the receipt of a message carrying the remote timestamp, the
+    /// result being after both the remote timestamp and the last local one.
+    pub fn update(&self, remote: &HlcTimestamp) -> Result<HlcTimestamp, ClockDriftError> {
+        self.update_at(remote, wall_clock())
+    }
+
+    fn now_at(&self, wall: u64) -> HlcTimestamp {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        *last = if wall > last.physical {
+            HlcTimestamp::new(wall, 0)
+        } else {
+            last.successor()
+        };
+        *last
+    }
+
+    fn update_at(&self, remote: &HlcTimestamp, wall: u64) -> Result<HlcTimestamp, ClockDriftError> {
+        let max_drift = self.max_drift.as_millis() as u64;
+        if remote.physical > wall.saturating_add(max_drift) {
+            return Err(ClockDriftError {
+                remote: *remote,
+                local: wall,
+                max_drift: self.max_drift,
+            });
+        }
+
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        let physical = wall.max(last.physical).max(remote.physical);
+        *last = if physical == wall && physical > last.physical && physical > remote.physical {
+            HlcTimestamp::new(wall, 0)
+        } else {
+            // The successor of the larger timestamp of the same millisecond.
+            let latest = [*last, *remote]
+                .into_iter()
+                .filter(|ts| ts.physical == physical)
+                .max()
+                .unwrap_or_default();
+            latest.successor()
+        };
+        Ok(*last)
+    }
+}
+
+impl IdGenerator for HybridLogicalClock {
+    type Id = HlcTimestamp;
+
+    fn next_id(&self) -> HlcTimestamp {
+        self.now()
+    }
+}
+
+fn wall_clock() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generator::tests::check_concurrent, IntoIdentifierIterator};
+
+    #[test]
+    fn local_events() {
+        let clock = HybridLogicalClock::new();
+        assert_eq!(clock.now_at(100), HlcTimestamp::new(100, 0));
+        assert_eq!(clock.now_at(100), HlcTimestamp::new(100, 1));
+        // the wall clock went back.
+        assert_eq!(clock.now_at(90), HlcTimestamp::new(100, 2));
+        assert_eq!(clock.now_at(101), HlcTimestamp::new(101, 0));
+        assert_eq!(clock.last(), HlcTimestamp::new(101, 0));
+    }
+
+    #[test]
+    fn receive() {
+        let clock = HybridLogicalClock::new();
+        clock.now_at(100);
+
+        // the remote clock is ahead.
+        let remote = HlcTimestamp::new(150, 3);
+        assert_eq!(clock.update_at(&remote, 120), Ok(HlcTimestamp::new(150, 4)));
+        assert_eq!(clock.now_at(120), HlcTimestamp::new(150, 5));
+
+        // the local clock is ahead.
+        let remote = HlcTimestamp::new(100, 7);
+        assert_eq!(clock.update_at(&remote, 120), Ok(HlcTimestamp::new(150, 6)));
+
+        // the wall clock is ahead of both.
+        assert_eq!(clock.update_at(&remote, 200), Ok(HlcTimestamp::new(200, 0)));
+
+        // same millisecond on both sides.
+        let remote = HlcTimestamp::new(200, 9);
+        assert_eq!(
+            clock.update_at(&remote, 200),
+            Ok(HlcTimestamp::new(200, 10))
+        );
+    }
+
+    #[test]
+    fn max_drift() {
+        let clock = HybridLogicalClock::new().with_max_drift(Duration::from_millis(50));
+        let remote = HlcTimestamp::new(151, 0);
+        let err = clock.update_at(&remote, 100).unwrap_err();
+        assert_eq!(err.local, 100);
+        assert_eq!(clock.last(), HlcTimestamp::default());
+
+        let remote = HlcTimestamp::new(150, 0);
+        assert_eq!(clock.update_at(&remote, 100), Ok(HlcTimestamp::new(150, 1)));
+    }
+
+    #[test]
+    fn wall_time() {
+        let clock = HybridLogicalClock::new();
+        let ts = clock.now();
+        assert!(wall_clock() - ts.physical() < 1000);
+        assert!(clock.update(&ts).unwrap() > ts);
+        assert!(clock
+            .update(&HlcTimestamp::new(u64::MAX, 0))
+            .unwrap_err()
+            .to_string()
+            .contains("ahead of the local clock"));
+    }
+
+    #[test]
+    fn successor() {
+        assert_eq!(
+            HlcTimestamp::new(5, u32::MAX).successor(),
+            HlcTimestamp::new(6, 0)
+        );
+
+        let mut ids = HlcTimestamp::new(5, 0).into_ids_iterator(HlcTimestamp::successor);
+        assert_eq!(ids.next(), Some(HlcTimestamp::new(5, 0)));
+        assert_eq!(ids.next(), Some(HlcTimestamp::new(5, 1)));
+    }
+
+    #[test]
+    fn parse() {
+        let ts = HlcTimestamp::new(1_700_000_000_000, 12);
+        assert_eq!(ts.to_string(), "1700000000000.12");
+        assert_eq!("1700000000000.12".parse(), Ok(ts));
+        assert!("1700000000000".parse::<HlcTimestamp>().is_err());
+        assert!("a.1".parse::<HlcTimestamp>().is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let ts = HlcTimestamp::new(100, 2);
+        let json = serde_json::to_string(&ts).unwrap();
+        assert_eq!(json, r#"{"physical":100,"logical":2}"#);
+        assert_eq!(serde_json::from_str::<HlcTimestamp>(&json).unwrap(), ts);
+    }
+
+    #[test]
+    fn concurrent() {
+        check_concurrent(HybridLogicalClock::new());
+    }
+}
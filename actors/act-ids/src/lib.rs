@@ -1,3 +1,27 @@
+mod clock;
+mod generator;
+mod hlc;
+mod snowflake;
+mod typed_id;
+#[cfg(feature = "ulid")]
+mod ulids;
+#[cfg(feature = "uuid")]
+mod uuids;
+
+pub use clock::*;
+pub use generator::*;
+pub use hlc::*;
+pub use snowflake::*;
+pub use typed_id::*;
+#[cfg(feature = "ulid")]
+pub use ulid::Ulid;
+#[cfg(feature = "ulid")]
+pub use ulids::*;
+#[cfg(feature = "uuid")]
+pub use uuid::Uuid;
+#[cfg(feature = "uuid")]
+pub use uuids::*;
+
 /// Identity trait represents data which can be used
 /// as identifiers. Given two identifiers we just need
 /// to determine if they are equal (represent the same entity)
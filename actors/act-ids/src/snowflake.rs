@@ -0,0 +1,177 @@
+use crate::IdGenerator;
+use std::{
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const NODE_BITS: u32 = 10;
+const SEQUENCE_BITS: u32 = 12;
+const TIMESTAMP_BITS: u32 = 41;
+
+/// The largest node number, 1023.
+pub const MAX_SNOWFLAKE_NODE: u16 = (1 << NODE_BITS) - 1;
+
+const MAX_SEQUENCE: u64 = (1 << SEQUENCE_BITS) - 1;
+
+/// The default epoch of the timestamps, 2024-01-01T00:00:00Z.
+const DEFAULT_EPOCH: Duration = Duration::from_millis(1_704_067_200_000);
+
+/// Hands out Snowflake identifiers: 64-bit numbers made of the milliseconds
+/// since an epoch (41 bits, about 69 years), the node number (10 bits) and
+/// a sequence within the millisecond (12 bits). The identifiers of a
+/// generator increase, those of different nodes are unique as long as the
+/// nodes have different numbers, and all of them sort by time.
+///
+/// When the 4096 identifiers of a millisecond are exhausted the generator
+/// moves on to the next millisecond without waiting for the clock. If the
+/// clock goes back, or the generator ran ahead of it, the generator keeps
+/// using the last timestamp until the clock catches up.
+#[derive(Debug)]
+pub struct SnowflakeGenerator {
+    node: u64,
+    epoch: SystemTime,
+    /// The timestamp and the sequence of the last identifier.
+    last: Mutex<(u64, u64)>,
+}
+
+impl SnowflakeGenerator {
+    /// Panics if the node number is above [`MAX_SNOWFLAKE_NODE`].
+    pub fn new(node: u16) -> Self {
+        assert!(
+            node <= MAX_SNOWFLAKE_NODE,
+            "the snowflake node must be at most {MAX_SNOWFLAKE_NODE}"
+        );
+        Self {
+            node: node as u64,
+            epoch: UNIX_EPOCH + DEFAULT_EPOCH,
+            last: Mutex::new((0, 0)),
+        }
+    }
+
+    /// Counts the timestamps from the given epoch, which must be in the past.
+    pub fn with_epoch(mut self, epoch: SystemTime) -> Self {
+        self.epoch = epoch;
+        self
+    }
+
+    pub fn node(&self) -> u16 {
+        self.node as u16
+    }
+
+    fn now(&self) -> u64 {
+        let elapsed = SystemTime::now()
+            .duration_since(self.epoch)
+            .unwrap_or_default();
+        elapsed.as_millis() as u64
+    }
+
+    /// Splits an identifier into its timestamp, node and sequence.
+    pub fn decompose(id: u64) -> (u64, u16, u16) {
+        let sequence = id & MAX_SEQUENCE;
+        let node = (id >> SEQUENCE_BITS) & MAX_SNOWFLAKE_NODE as u64;
+        let timestamp = id >> (SEQUENCE_BITS + NODE_BITS);
+        (timestamp, node as u16, sequence as u16)
+    }
+}
+
+impl IdGenerator for SnowflakeGenerator {
+    type Id = u64;
+
+    /// Panics once the timestamps overflow their 41 bits.
+    fn next_id(&self) -> u64 {
+        self.next_at(self.now())
+    }
+}
+
+impl SnowflakeGenerator {
+    fn next_at(&self, now: u64) -> u64 {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        let (last_timestamp, last_sequence) = *last;
+
+        let (timestamp, sequence) = if now > last_timestamp {
+            (now, 0)
+        } else if last_sequence < MAX_SEQUENCE {
+            (last_timestamp, last_sequence + 1)
+        } else {
+            (last_timestamp + 1, 0)
+        };
+        assert!(
+            timestamp >> TIMESTAMP_BITS == 0,
+            "the snowflake timestamps are exhausted"
+        );
+
+        *last = (timestamp, sequence);
+        (timestamp << (SEQUENCE_BITS + NODE_BITS)) | (self.node << SEQUENCE_BITS) | sequence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::tests::check_concurrent;
+
+    #[test]
+    fn layout() {
+        let generator = SnowflakeGenerator::new(5);
+        let first = generator.next_id();
+        let second = generator.next_id();
+        assert!(first < second);
+
+        let (timestamp, node, _) = SnowflakeGenerator::decompose(second);
+        assert_eq!(node, 5);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH + DEFAULT_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        assert!(now - timestamp < 1000);
+    }
+
+    #[test]
+    fn sequence_overflow() {
+        let generator = SnowflakeGenerator::new(MAX_SNOWFLAKE_NODE);
+        let ids = generator.ids().take(3 * 4096).collect::<Vec<_>>();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+
+        // no millisecond holds more than 4096 identifiers.
+        let (first, _, _) = SnowflakeGenerator::decompose(ids[0]);
+        let (last, _, _) = SnowflakeGenerator::decompose(ids[ids.len() - 1]);
+        assert!(last >= first + 2);
+    }
+
+    #[test]
+    fn clock_back() {
+        let generator = SnowflakeGenerator::new(1);
+        let first = generator.next_at(100);
+        for _ in 0..MAX_SEQUENCE {
+            generator.next_at(90);
+        }
+        // the sequence overflowed: the generator runs ahead of the clock.
+        let ahead = generator.next_at(90);
+        assert_eq!(SnowflakeGenerator::decompose(ahead), (101, 1, 0));
+        assert!(ahead > first);
+
+        assert_eq!(generator.next_at(101), ahead + 1);
+        assert_eq!(
+            SnowflakeGenerator::decompose(generator.next_at(102)),
+            (102, 1, 0)
+        );
+    }
+
+    #[test]
+    fn nodes_do_not_collide() {
+        let a = SnowflakeGenerator::new(1);
+        let b = SnowflakeGenerator::new(2);
+        assert_ne!(a.next_id(), b.next_id());
+    }
+
+    #[test]
+    #[should_panic(expected = "at most 1023")]
+    fn invalid_node() {
+        SnowflakeGenerator::new(1024);
+    }
+
+    #[test]
+    fn concurrent() {
+        check_concurrent(SnowflakeGenerator::new(1));
+    }
+}
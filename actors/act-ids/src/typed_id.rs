@@ -0,0 +1,504 @@
+use std::{
+    cmp::Ordering,
+    fmt::{Debug, Display},
+    hash::Hash,
+    marker::PhantomData,
+    str::FromStr,
+};
+
+/// The formats of the bodies of typed identifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdFormat {
+    /// A non-empty string of Crockford base32 digits, kept in lowercase.
+    Base32,
+    /// A ULID: 26 Crockford base32 digits, kept in uppercase.
+    Ulid,
+    /// A hyphenated UUID, kept in lowercase.
+    Uuid,
+}
+
+impl IdFormat {
+    /// Checks the body and returns its canonical form.
+    fn canonical(&self, body: &str) -> Option<String> {
+        let valid = match self {
+            IdFormat::Base32 => !body.is_empty() && body.chars().all(is_crockford_digit),
+            IdFormat::Ulid => {
+                // 26 digits hold 130 bits, the first one holds 3 of the 128.
+                body.len() == 26
+                    && body.chars().all(is_crockford_digit)
+                    && body.as_bytes()[0] <= b'7'
+            }
+            IdFormat::Uuid => {
+                body.len() == 36
+                    && body.char_indices().all(|(i, c)| match i {
+                        8 | 13 | 18 | 23 => c == '-',
+                        _ => c.is_ascii_hexdigit(),
+                    })
+            }
+        };
+        if !valid {
+            return None;
+        }
+        match self {
+            IdFormat::Ulid => Some(body.to_ascii_uppercase()),
+            IdFormat::Base32 | IdFormat::Uuid => Some(body.to_ascii_lowercase()),
+        }
+    }
+}
+
+/// The Crockford base32 digits: the decimal digits and the letters but
+/// I, L, O and U, in any case.
+fn is_crockford_digit(c: char) -> bool {
+    c.is_ascii_digit()
+        || (c.is_ascii_alphabetic() && !matches!(c.to_ascii_uppercase(), 'I' | 'L' | 'O' | 'U'))
+}
+
+/// The IdPrefix trait ties a kind of identifiers to its prefix and to
+/// the format of its bodies. The prefix must not be empty nor contain
+/// the `_` separator.
+///
+/// ```
+/// use act_ids::{IdFormat, IdPrefix, TypedId};
+///
+/// struct Order;
+/// impl IdPrefix for Order {
+///     const PREFIX: &'static str = "ord";
+///     const FORMAT: IdFormat = IdFormat::Ulid;
+/// }
+/// type OrderId = TypedId<Order>;
+///
+/// let id: OrderId = "ord_01HGW2N7EHJVZJ8Q5Y2X3V4B6C".parse().unwrap();
+/// assert_eq!(id.body(), "01HGW2N7EHJVZJ8Q5Y2X3V4B6C");
+/// ```
+///
+/// An invalid prefix fails the build as soon as an identifier is built:
+///
+/// ```compile_fail
+/// use act_ids::{IdFormat, IdPrefix, TypedId};
+///
+/// struct LineItem;
+/// impl IdPrefix for LineItem {
+///     const PREFIX: &'static str = "line_item";
+///     const FORMAT: IdFormat = IdFormat::Base32;
+/// }
+///
+/// let id = TypedId::<LineItem>::from_body("a1");
+/// ```
+pub trait IdPrefix {
+    const PREFIX: &'static str;
+    const FORMAT: IdFormat;
+}
+
+/// The separator between the prefix and the body of typed identifiers.
+pub const TYPED_ID_SEPARATOR: char = '_';
+
+/// Returns `true` if the prefix is not empty and has no separator.
+const fn is_valid_prefix(prefix: &str) -> bool {
+    let bytes = prefix.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == TYPED_ID_SEPARATOR as u8 {
+            return false;
+        }
+        i += 1;
+    }
+    !bytes.is_empty()
+}
+
+/// A string identifier made of a prefix, the `_` separator and a body,
+/// like `ord_01HGW2N7EHJVZJ8Q5Y2X3V4B6C`. The prefix and the body format
+/// come from the type parameter, so the identifiers of different kinds
+/// are different types and an identifier with the wrong prefix is
+/// rejected when parsed.
+///
+/// The bodies are kept in their canonical case, so the identifiers which
+/// differ only by case are equal. The identifiers are ordered as strings,
+/// which orders the ULID ones by time.
+pub struct TypedId<P>
+where
+    P: IdPrefix,
+{
+    id: String,
+    prefix: PhantomData<fn() -> P>,
+}
+
+impl<P> TypedId<P>
+where
+    P: IdPrefix,
+{
+    /// Evaluated at compile time for every prefix type an identifier is
+    /// built with, so an invalid prefix fails the build.
+    const VALID_PREFIX: () = assert!(
+        is_valid_prefix(P::PREFIX),
+        "the prefix of typed ids must not be empty nor contain the `_` separator"
+    );
+
+    /// Builds an identifier from its body, without the prefix.
+    pub fn from_body(body: &str) -> Result<Self, ParseTypedIdError> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID_PREFIX;
+        let body = P::FORMAT
+            .canonical(body)
+            .ok_or_else(|| ParseTypedIdError::Body(P::FORMAT, body.to_string()))?;
+        Ok(Self {
+            id: format!("{}{TYPED_ID_SEPARATOR}{body}", P::PREFIX),
+            prefix: PhantomData,
+        })
+    }
+
+    pub fn prefix(&self) -> &'static str {
+        P::PREFIX
+    }
+
+    pub fn body(&self) -> &str {
+        &self.id[P::PREFIX.len() + TYPED_ID_SEPARATOR.len_utf8()..]
+    }
+
+    /// The whole identifier, prefix included.
+    pub fn as_str(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(feature = "ulid")]
+impl<P> TypedId<P>
+where
+    P: IdPrefix,
+{
+    /// Builds an identifier from a ULID, panics if the bodies of the
+    /// identifiers are not ULIDs.
+    pub fn from_ulid(ulid: ulid::Ulid) -> Self {
+        assert!(
+            P::FORMAT == IdFormat::Ulid,
+            "the bodies of the {} identifiers are not ULIDs",
+            P::PREFIX
+        );
+        Self::from_body(&ulid.to_string()).expect("a ULID is a valid body")
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl<P> TypedId<P>
+where
+    P: IdPrefix,
+{
+    /// Builds an identifier from a UUID, panics if the bodies of the
+    /// identifiers are not UUIDs.
+    pub fn from_uuid(uuid: uuid::Uuid) -> Self {
+        assert!(
+            P::FORMAT == IdFormat::Uuid,
+            "the bodies of the {} identifiers are not UUIDs",
+            P::PREFIX
+        );
+        Self::from_body(&uuid.to_string()).expect("a UUID is a valid body")
+    }
+}
+
+//
+// Traits implemented without bounds on the prefix type
+//
+
+impl<P> Clone for TypedId<P>
+where
+    P: IdPrefix,
+{
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            prefix: PhantomData,
+        }
+    }
+}
+
+impl<P> PartialEq for TypedId<P>
+where
+    P: IdPrefix,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<P> Eq for TypedId<P> where P: IdPrefix {}
+
+impl<P> PartialOrd for TypedId<P>
+where
+    P: IdPrefix,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<P> Ord for TypedId<P>
+where
+    P: IdPrefix,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+impl<P> Hash for TypedId<P>
+where
+    P: IdPrefix,
+{
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<P> Debug for TypedId<P>
+where
+    P: IdPrefix,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TypedId").field(&self.id).finish()
+    }
+}
+
+impl<P> Display for TypedId<P>
+where
+    P: IdPrefix,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+impl<P> FromStr for TypedId<P>
+where
+    P: IdPrefix,
+{
+    type Err = ParseTypedIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, body) = s
+            .split_once(TYPED_ID_SEPARATOR)
+            .ok_or_else(|| ParseTypedIdError::Format(s.to_string()))?;
+        if prefix != P::PREFIX {
+            return Err(ParseTypedIdError::Prefix {
+                expected: P::PREFIX,
+                found: prefix.to_string(),
+            });
+        }
+        Self::from_body(body)
+    }
+}
+
+impl<P> AsRef<str> for TypedId<P>
+where
+    P: IdPrefix,
+{
+    fn as_ref(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<P> serde::Serialize for TypedId<P>
+where
+    P: IdPrefix,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.id)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, P> serde::Deserialize<'de> for TypedId<P>
+where
+    P: IdPrefix,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// The string is not an identifier of the expected kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseTypedIdError {
+    /// The separator is missing.
+    Format(String),
+    /// The prefix is not the one of the identifiers.
+    Prefix {
+        expected: &'static str,
+        found: String,
+    },
+    /// The body does not have the format of the identifiers.
+    Body(IdFormat, String),
+}
+
+impl Display for ParseTypedIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseTypedIdError::Format(s) => write!(f, "Invalid typed id [{s}]"),
+            ParseTypedIdError::Prefix { expected, found } => {
+                write!(
+                    f,
+                    "Invalid typed id prefix [{found}], expected [{expected}]"
+                )
+            }
+            ParseTypedIdError::Body(format, body) => {
+                write!(f, "Invalid typed id body [{body}], expected {format:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseTypedIdError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PartialOrdIdentifier;
+    use std::collections::HashSet;
+
+    struct Order;
+    impl IdPrefix for Order {
+        const PREFIX: &'static str = "ord";
+        const FORMAT: IdFormat = IdFormat::Ulid;
+    }
+
+    struct User;
+    impl IdPrefix for User {
+        const PREFIX: &'static str = "user";
+        const FORMAT: IdFormat = IdFormat::Uuid;
+    }
+
+    struct Session;
+    impl IdPrefix for Session {
+        const PREFIX: &'static str = "sess";
+        const FORMAT: IdFormat = IdFormat::Base32;
+    }
+
+    type OrderId = TypedId<Order>;
+    type UserId = TypedId<User>;
+    type SessionId = TypedId<Session>;
+
+    fn test_partialord_identifier(_id: impl PartialOrdIdentifier) {}
+
+    #[test]
+    fn parse_ulid() {
+        let id: OrderId = "ord_01hgw2n7ehjvzj8q5y2x3v4b6c".parse().unwrap();
+        assert_eq!(id.prefix(), "ord");
+        assert_eq!(id.body(), "01HGW2N7EHJVZJ8Q5Y2X3V4B6C");
+        assert_eq!(id.to_string(), "ord_01HGW2N7EHJVZJ8Q5Y2X3V4B6C");
+        assert_eq!(id, "ord_01HGW2N7EHJVZJ8Q5Y2X3V4B6C".parse().unwrap());
+        test_partialord_identifier(id);
+
+        // too long, above the 128 bits, and not base32.
+        for body in [
+            "01HGW2N7EHJVZJ8Q5Y2X3V4B6CD",
+            "81HGW2N7EHJVZJ8Q5Y2X3V4B6C",
+            "01HGW2N7EHJVZJ8Q5Y2X3V4B6U",
+        ] {
+            assert_eq!(
+                format!("ord_{body}").parse::<OrderId>(),
+                Err(ParseTypedIdError::Body(IdFormat::Ulid, body.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn parse_uuid() {
+        let id: UserId = "user_67E55044-10B1-426F-9247-BB680E5FE0C8".parse().unwrap();
+        assert_eq!(id.as_str(), "user_67e55044-10b1-426f-9247-bb680e5fe0c8");
+        assert!("user_67e55044-10b1-426f-9247-bb680e5fe0c"
+            .parse::<UserId>()
+            .is_err());
+        assert!("user_67e55044_10b1-426f-9247-bb680e5fe0c8"
+            .parse::<UserId>()
+            .is_err());
+        assert!("user_g7e55044-10b1-426f-9247-bb680e5fe0c8"
+            .parse::<UserId>()
+            .is_err());
+    }
+
+    #[test]
+    fn parse_base32() {
+        let id: SessionId = "sess_A1B2".parse().unwrap();
+        assert_eq!(id.body(), "a1b2");
+        assert!("sess_".parse::<SessionId>().is_err());
+        assert!("sess_a1-b2".parse::<SessionId>().is_err());
+        assert!("sess_io".parse::<SessionId>().is_err());
+    }
+
+    #[test]
+    fn wrong_kind() {
+        assert_eq!(
+            "user_01HGW2N7EHJVZJ8Q5Y2X3V4B6C".parse::<OrderId>(),
+            Err(ParseTypedIdError::Prefix {
+                expected: "ord",
+                found: "user".to_string()
+            })
+        );
+        assert_eq!(
+            "01HGW2N7EHJVZJ8Q5Y2X3V4B6C".parse::<OrderId>(),
+            Err(ParseTypedIdError::Format(
+                "01HGW2N7EHJVZJ8Q5Y2X3V4B6C".to_string()
+            ))
+        );
+        assert_eq!(
+            "user_a1".parse::<OrderId>().unwrap_err().to_string(),
+            "Invalid typed id prefix [user], expected [ord]"
+        );
+    }
+
+    #[test]
+    fn valid_prefixes() {
+        assert!(is_valid_prefix("ord"));
+        assert!(is_valid_prefix("line-item"));
+        assert!(!is_valid_prefix(""));
+        assert!(!is_valid_prefix("line_item"));
+        assert!(!is_valid_prefix("_"));
+    }
+
+    #[test]
+    fn ord_and_hash() {
+        let a = OrderId::from_body("01HGW2N7EHJVZJ8Q5Y2X3V4B6C").unwrap();
+        let b = OrderId::from_body("01HGW2N7EHJVZJ8Q5Y2X3V4B6D").unwrap();
+        assert!(a < b);
+
+        let ids = HashSet::from([a.clone(), b, a.clone()]);
+        assert_eq!(ids.len(), 2);
+        assert_eq!(
+            format!("{a:?}"),
+            r#"TypedId("ord_01HGW2N7EHJVZJ8Q5Y2X3V4B6C")"#
+        );
+    }
+
+    #[cfg(feature = "ulid")]
+    #[test]
+    fn from_ulid() {
+        let ulid = ulid::Ulid::new();
+        let id = OrderId::from_ulid(ulid);
+        assert_eq!(id.body(), ulid.to_string());
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn from_uuid() {
+        let uuid = uuid::Uuid::new_v4();
+        let id = UserId::from_uuid(uuid);
+        assert_eq!(id.body(), uuid.to_string());
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    #[should_panic(expected = "are not UUIDs")]
+    fn from_uuid_wrong_format() {
+        OrderId::from_uuid(uuid::Uuid::new_v4());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let id = SessionId::from_body("a1b2").unwrap();
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, r#""sess_a1b2""#);
+        assert_eq!(serde_json::from_str::<SessionId>(&json).unwrap(), id);
+        assert!(serde_json::from_str::<OrderId>(&json).is_err());
+    }
+}
@@ -0,0 +1,57 @@
+use crate::IdGenerator;
+use std::sync::Mutex;
+use ulid::{Generator, Ulid};
+
+/// Hands out ULIDs: the milliseconds since the Unix epoch followed by
+/// random bits, which sort by time. The ULIDs of a generator increase:
+/// within a millisecond the random bits of the previous one are incremented.
+#[derive(Default)]
+pub struct UlidGenerator {
+    generator: Mutex<Generator>,
+}
+
+impl UlidGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl std::fmt::Debug for UlidGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UlidGenerator").finish_non_exhaustive()
+    }
+}
+
+impl IdGenerator for UlidGenerator {
+    type Id = Ulid;
+
+    fn next_id(&self) -> Ulid {
+        let mut generator = self.generator.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            match generator.generate() {
+                Ok(id) => return id,
+                // The random bits of this millisecond are exhausted.
+                Err(_) => std::thread::yield_now(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::tests::check_concurrent;
+
+    #[test]
+    fn monotonic() {
+        let generator = UlidGenerator::new();
+        let ids = generator.ids().take(1000).collect::<Vec<_>>();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(ids[0].to_string().len(), 26);
+    }
+
+    #[test]
+    fn concurrent() {
+        check_concurrent(UlidGenerator::new());
+    }
+}
@@ -0,0 +1,55 @@
+use crate::IdGenerator;
+use uuid::Uuid;
+
+/// Hands out random UUIDs (version 4). They are unique with an
+/// overwhelming probability, but they do not sort by time.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct UuidV4Generator;
+
+impl IdGenerator for UuidV4Generator {
+    type Id = Uuid;
+
+    fn next_id(&self) -> Uuid {
+        Uuid::new_v4()
+    }
+}
+
+/// Hands out time-ordered UUIDs (version 7): the milliseconds since the
+/// Unix epoch followed by random bits. The UUIDs generated in the process
+/// increase, even within a millisecond.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct UuidV7Generator;
+
+impl IdGenerator for UuidV7Generator {
+    type Id = Uuid;
+
+    fn next_id(&self) -> Uuid {
+        Uuid::now_v7()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::tests::check_concurrent;
+    use std::collections::HashSet;
+
+    #[test]
+    fn v4() {
+        let ids = UuidV4Generator.ids().take(100).collect::<HashSet<_>>();
+        assert_eq!(ids.len(), 100);
+        assert!(ids.iter().all(|id| id.get_version_num() == 4));
+    }
+
+    #[test]
+    fn v7() {
+        let ids = UuidV7Generator.ids().take(1000).collect::<Vec<_>>();
+        assert!(ids.iter().all(|id| id.get_version_num() == 7));
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn v7_concurrent() {
+        check_concurrent(UuidV7Generator);
+    }
+}
@@ -1,18 +1,26 @@
-use crate::Message;
-use act_identifiers::ActorId;
+use crate::{
+    mailbox, Bus, Context, ExitReason, Handler, MailboxConfig, MailboxReceiver, MailboxSender,
+    Message, Metrics,
+};
+use act_identifiers::{ActorId, ToId};
 use anyhow::Result;
-use std::fmt::Debug;
-use tokio::sync::mpsc::{channel, Receiver, Sender};
+use std::{fmt::Debug, future::Future, sync::Arc, time::Duration};
+use tokio::{
+    sync::{mpsc::Sender, watch},
+    task::JoinHandle,
+    time::Instant,
+};
 
-pub struct Actor<A>
+pub struct Actor<A, P>
 where
     A: ActorId + Debug,
 {
     pub(crate) aid: A,
-    inbound_tx: Sender<Message<A>>,
+    inbound_tx: MailboxSender<A, P>,
+    bus: Bus<A, P>,
 }
 
-impl<A> Actor<A>
+impl<A, P> Actor<A, P>
 where
     A: ActorId + Debug,
 {
@@ -20,40 +28,435 @@ where
         &self.aid
     }
 
-    pub fn tx(&self) -> Sender<Message<A>> {
+    pub fn tx(&self) -> MailboxSender<A, P> {
         self.inbound_tx.clone()
     }
 }
 
-impl<A> Actor<A>
+impl<A, P> Actor<A, P>
 where
-    A: ActorId + Debug,
+    A: ActorId + Clone + Debug,
+{
+    /// Sends the payload, on behalf of this actor, to the given actor and
+    /// waits for its reply. The reply is returned to the caller and it is
+    /// not passed to the handler of this actor.
+    pub async fn ask<T>(&self, t: T, payload: P, timeout: Duration) -> Result<Message<A, P>>
+    where
+        T: Into<ToId<A>>,
+    {
+        let msg = Message::new(self.aid.clone(), t, payload);
+        self.bus.ask(msg, timeout).await
+    }
+
+    /// Subscribes this actor to the topic, see [`Bus::subscribe`].
+    pub async fn subscribe<T>(&self, topic: T) -> Result<()>
+    where
+        T: Into<String>,
+    {
+        self.bus.subscribe(topic, self.aid.clone()).await
+    }
+
+    /// Unsubscribes this actor from the topic.
+    pub async fn unsubscribe<T>(&self, topic: T) -> Result<()>
+    where
+        T: Into<String>,
+    {
+        self.bus.unsubscribe(topic, self.aid.clone()).await
+    }
+}
+
+impl<A, P> Actor<A, P>
+where
+    A: ActorId + Debug + 'static,
 {
-    async fn internal_loop(mut inbound_rx: Receiver<Message<A>>, _outbound_tx: Sender<Message<A>>) {
-        loop {
+    async fn internal_loop<H>(
+        mut handler: H,
+        mut ctx: Context<A, P>,
+        mut inbound_rx: MailboxReceiver<A, P>,
+        outbound_tx: Sender<Message<A, P>>,
+        mut stop_rx: watch::Receiver<bool>,
+        metrics: Arc<dyn Metrics<A>>,
+    ) where
+        H: Handler<A, P>,
+    {
+        handler.started(&mut ctx);
+        Self::flush(&mut ctx, &outbound_tx).await;
+
+        while !ctx.is_stopping() {
             tokio::select! {
                 msg = inbound_rx.recv() => {
                     match msg {
-                        Some(_msg) => {
-                            todo!("Actor inbound handle not implemented")
+                        Some(msg) => {
+                            let span = tracing::debug_span!(
+                                "handle",
+                                fid = ?msg.fid().as_ref(),
+                                tid = ?msg.tid().as_ref(),
+                                mid = ?msg.mid(),
+                            );
+                            // The handler latency is wall time, it keeps
+                            // counting when tokio's clock is paused.
+                            let start = std::time::Instant::now();
+                            span.in_scope(|| handler.handle(msg, &mut ctx));
+                            metrics.handler_latency(ctx.aid(), start.elapsed());
+                            Self::flush(&mut ctx, &outbound_tx).await;
                         }
                         None => break,
                     }
                 }
+                Ok(()) = stop_rx.changed() => {
+                    // Refuse any new message, but handle the ones already received.
+                    inbound_rx.close();
+                }
+            }
+        }
+
+        handler.stopping(&mut ctx);
+        Self::flush(&mut ctx, &outbound_tx).await;
+
+        handler.stopped();
+    }
+
+    /// Forwards to the bus all the messages the handler sent.
+    async fn flush(ctx: &mut Context<A, P>, outbound_tx: &Sender<Message<A, P>>) {
+        for msg in ctx.drain() {
+            if outbound_tx.send(msg).await.is_err() {
+                // The bus is gone, nobody is going to route the rest.
+                break;
             }
         }
     }
 }
 
-impl<A> Actor<A>
+impl<A, P> Actor<A, P>
 where
-    A: ActorId + Debug + Send + 'static,
+    A: ActorId + Clone + Debug + Send + 'static,
+    P: Send + 'static,
 {
-    pub async fn spawn(aid: A, outbound_tx: Sender<Message<A>>) -> Result<Self> {
-        let (inbound_tx, inbound_rx) = channel::<Message<A>>(10);
+    /// Spawns the actor task. The messages the handler sends are
+    /// forwarded to the given bus.
+    pub async fn spawn<H>(aid: A, handler: H, bus: &Bus<A, P>) -> Result<Self>
+    where
+        H: Handler<A, P>,
+    {
+        Self::spawn_with_mailbox(aid, handler, MailboxConfig::default(), bus).await
+    }
+
+    /// Spawns the actor task with a mailbox built from the given configuration.
+    pub async fn spawn_with_mailbox<H>(
+        aid: A,
+        handler: H,
+        config: MailboxConfig,
+        bus: &Bus<A, P>,
+    ) -> Result<Self>
+    where
+        H: Handler<A, P>,
+    {
+        let (actor, _cell) = Self::start(aid, handler, config, bus);
+        Ok(actor)
+    }
 
-        tokio::spawn(Self::internal_loop(inbound_rx, outbound_tx));
+    /// Spawns the actor task, returning as well the cell which allows
+    /// the caller to stop the actor and to observe when it stops or panics.
+    pub(crate) fn start<H>(
+        aid: A,
+        handler: H,
+        config: MailboxConfig,
+        bus: &Bus<A, P>,
+    ) -> (Self, ActorCell<A>)
+    where
+        H: Handler<A, P>,
+    {
+        let (inbound_tx, inbound_rx) = mailbox::<A, P>(config);
+        let inbound_rx = inbound_rx.with_dead_letters(bus);
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let ctx = Context::new(aid.clone(), bus.bus_tx());
+
+        let internal_loop = Self::internal_loop(
+            handler,
+            ctx,
+            inbound_rx,
+            bus.bus_tx(),
+            stop_rx,
+            bus.metrics(),
+        );
+        let (handle, exit_rx) = spawn_watched(internal_loop);
+
+        let cell = ActorCell {
+            aid: aid.clone(),
+            stop_tx,
+            handle,
+            exit_rx,
+        };
+        let actor = Self {
+            aid,
+            inbound_tx,
+            bus: bus.clone(),
+        };
+        (actor, cell)
+    }
+}
+
+/// The parts of a running actor which its owner needs to stop it.
+pub(crate) struct ActorCell<A> {
+    pub(crate) aid: A,
+    pub(crate) stop_tx: watch::Sender<bool>,
+    pub(crate) handle: JoinHandle<()>,
+    /// Receives how the actor task ended, once it did.
+    pub(crate) exit_rx: watch::Receiver<Option<ExitReason>>,
+}
+
+impl<A> ActorCell<A> {
+    /// Asks the actor to stop once it handled the messages already
+    /// in its mailbox. Messages sent afterwards are refused.
+    pub(crate) fn signal_stop(&self) {
+        let _ = self.stop_tx.send(true);
+    }
+
+    /// Waits for the actor task to end. If the task is still running at
+    /// the deadline it is aborted. Returns `true` if it ended in time.
+    pub(crate) async fn join(&mut self, deadline: Instant) -> bool {
+        match tokio::time::timeout_at(deadline, &mut self.handle).await {
+            Ok(_) => true,
+            Err(_) => {
+                self.handle.abort();
+                false
+            }
+        }
+    }
+}
+
+/// Spawns the task, publishing on the returned receiver how it ended.
+pub(crate) fn spawn_watched<F>(task: F) -> (JoinHandle<()>, watch::Receiver<Option<ExitReason>>)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let (exit_tx, exit_rx) = watch::channel(None);
+    let handle = tokio::spawn(async move {
+        let mut guard = ExitGuard {
+            exit_tx,
+            stopped: false,
+        };
+        task.await;
+        guard.stopped = true;
+    });
+    (handle, exit_rx)
+}
+
+/// Publishes how the actor task ended when the task drops it: either
+/// after the loop returned, or while the task is torn down by a panic or
+/// an abort.
+struct ExitGuard {
+    exit_tx: watch::Sender<Option<ExitReason>>,
+    stopped: bool,
+}
+
+impl Drop for ExitGuard {
+    fn drop(&mut self) {
+        let reason = if self.stopped {
+            ExitReason::Stopped
+        } else {
+            ExitReason::Failed
+        };
+        self.exit_tx.send_replace(Some(reason));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DeadLetterReason;
+    use tokio::sync::mpsc::channel;
+
+    struct Counter {
+        count: usize,
+        events: Sender<String>,
+    }
+
+    impl Handler<u8, usize> for Counter {
+        fn started(&mut self, _ctx: &mut Context<u8, usize>) {
+            self.events.try_send("started".to_string()).unwrap();
+        }
+
+        fn handle(&mut self, msg: Message<u8, usize>, ctx: &mut Context<u8, usize>) {
+            self.count += msg.payload();
+            ctx.reply(&msg, self.count);
+            if self.count >= 10 {
+                ctx.stop();
+            }
+        }
+
+        fn stopping(&mut self, _ctx: &mut Context<u8, usize>) {
+            self.events.try_send("stopping".to_string()).unwrap();
+        }
+
+        fn stopped(&mut self) {
+            self.events.try_send("stopped".to_string()).unwrap();
+        }
+    }
+
+    #[derive(Default)]
+    struct Latencies(std::sync::Mutex<Vec<u8>>);
+
+    impl Metrics<u8> for Latencies {
+        fn handler_latency(&self, aid: &u8, _latency: Duration) {
+            self.0.lock().unwrap().push(*aid);
+        }
+    }
+
+    #[tokio::test]
+    async fn handler_latency() {
+        let latencies = Arc::new(Latencies::default());
+        let mut bus = Bus::<u8, usize>::spawn_with_metrics(10, latencies.clone())
+            .await
+            .unwrap();
+        let (outbound_tx, mut outbound_rx) = mailbox::<u8, usize>(MailboxConfig::default());
+        bus.add_actor(2, outbound_tx).await.unwrap();
+
+        let handler = |msg: Message<u8, usize>, ctx: &mut Context<u8, usize>| {
+            ctx.reply(&msg, *msg.payload());
+        };
+        let actor = Actor::spawn(1, handler, &bus).await.unwrap();
+        actor.tx().send(Message::new(2, 1, 4)).await.unwrap();
+        outbound_rx.recv().await.unwrap();
+
+        assert_eq!(*latencies.0.lock().unwrap(), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn lifecycle() {
+        let (events_tx, mut events_rx) = channel::<String>(10);
+        let mut bus = Bus::<u8, usize>::spawn().await.unwrap();
+        let (outbound_tx, mut outbound_rx) = mailbox::<u8, usize>(MailboxConfig::default());
+        bus.add_actor(2, outbound_tx).await.unwrap();
+
+        let handler = Counter {
+            count: 0,
+            events: events_tx,
+        };
+        let actor = Actor::spawn(1, handler, &bus).await.unwrap();
+        assert_eq!(events_rx.recv().await.unwrap(), "started");
+
+        actor.tx().send(Message::new(2, 1, 4)).await.unwrap();
+        let reply = outbound_rx.recv().await.unwrap();
+        assert_eq!(reply.tid().as_ref(), &2);
+        assert_eq!(reply.payload(), &4);
+
+        actor.tx().send(Message::new(2, 1, 6)).await.unwrap();
+        let reply = outbound_rx.recv().await.unwrap();
+        assert_eq!(reply.payload(), &10);
+
+        assert_eq!(events_rx.recv().await.unwrap(), "stopping");
+        assert_eq!(events_rx.recv().await.unwrap(), "stopped");
+    }
+
+    #[tokio::test]
+    async fn stop_when_inbound_closed() {
+        let (events_tx, mut events_rx) = channel::<String>(10);
+        let bus = Bus::<u8, usize>::spawn().await.unwrap();
+
+        let handler = Counter {
+            count: 0,
+            events: events_tx,
+        };
+        let actor = Actor::spawn(1, handler, &bus).await.unwrap();
+        drop(actor);
+
+        assert_eq!(events_rx.recv().await.unwrap(), "started");
+        assert_eq!(events_rx.recv().await.unwrap(), "stopping");
+        assert_eq!(events_rx.recv().await.unwrap(), "stopped");
+    }
+
+    #[tokio::test]
+    async fn ask() {
+        let (events_tx, _events_rx) = channel::<String>(10);
+        let mut bus = Bus::<u8, usize>::spawn().await.unwrap();
+
+        let handler = Counter {
+            count: 0,
+            events: events_tx,
+        };
+        let counter = Actor::spawn(1, handler, &bus).await.unwrap();
+        bus.add_actor(1, counter.tx()).await.unwrap();
+
+        let asker = Actor::spawn(2, |_msg, _ctx: &mut Context<u8, usize>| {}, &bus)
+            .await
+            .unwrap();
+        bus.add_actor(2, asker.tx()).await.unwrap();
+
+        let reply = asker.ask(1, 3, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(reply.fid().as_ref(), &1);
+        assert_eq!(reply.payload(), &3);
+
+        let reply = asker.ask(1, 4, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(reply.payload(), &7);
+    }
+
+    #[tokio::test]
+    async fn drain_on_stop() {
+        let (events_tx, mut events_rx) = channel::<String>(10);
+        let mut bus = Bus::<u8, usize>::spawn().await.unwrap();
+
+        let handler = Counter {
+            count: 0,
+            events: events_tx,
+        };
+        let (actor, mut cell) = Actor::start(1, handler, MailboxConfig::default(), &bus);
+        assert_eq!(events_rx.recv().await.unwrap(), "started");
+
+        for _ in 0..3 {
+            actor.tx().send(Message::new(2, 1, 1)).await.unwrap();
+        }
+        cell.signal_stop();
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        assert!(cell.join(deadline).await);
+        assert!(actor.tx().send(Message::new(2, 1, 1)).await.is_err());
+
+        assert_eq!(events_rx.recv().await.unwrap(), "stopping");
+        assert_eq!(events_rx.recv().await.unwrap(), "stopped");
+
+        // the replies went to the unregistered actor 2.
+        let replies = bus.dead_letters().await.unwrap();
+        let counts = replies
+            .iter()
+            .map(|dead_letter| *dead_letter.msg.payload())
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn leftovers_to_dead_letters() {
+        let (events_tx, mut events_rx) = channel::<String>(10);
+        let mut bus = Bus::<u8, usize>::spawn().await.unwrap();
+        let (outbound_tx, mut outbound_rx) = mailbox::<u8, usize>(MailboxConfig::default());
+        bus.add_actor(2, outbound_tx).await.unwrap();
+
+        let handler = Counter {
+            count: 0,
+            events: events_tx,
+        };
+        let (actor, mut cell) = Actor::start(1, handler, MailboxConfig::default(), &bus);
+        assert_eq!(events_rx.recv().await.unwrap(), "started");
+
+        // the actor stops on the first message, the others are left.
+        for payload in [10, 1, 2] {
+            actor.tx().send(Message::new(2, 1, payload)).await.unwrap();
+        }
+        let deadline = Instant::now() + Duration::from_secs(1);
+        assert!(cell.join(deadline).await);
+        assert_eq!(outbound_rx.recv().await.unwrap().into_payload(), 10);
 
-        Ok(Self { aid, inbound_tx })
+        let dead_letters = bus.dead_letters().await.unwrap();
+        let leftovers = dead_letters
+            .iter()
+            .map(|dead_letter| (*dead_letter.msg.payload(), dead_letter.reason))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            leftovers,
+            vec![
+                (1, DeadLetterReason::ActorStopped),
+                (2, DeadLetterReason::ActorStopped)
+            ]
+        );
     }
 }
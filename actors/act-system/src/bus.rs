@@ -1,31 +1,297 @@
-use crate::{CtrlMessage, HealthResponse, Message, QuitResponse};
-use act_identifiers::ActorId;
+use crate::timer;
+use crate::{
+    ActorHealth, ActorStatus, AddActorResponse, AskResponse, CtrlMessage, DeadLetter,
+    DeadLetterEvent, DeadLetterReason, HealthResponse, MailboxError, MailboxSender, Message,
+    Metrics, NoopMetrics, OverflowPolicy, QuitResponse, TimerHandle,
+};
+use act_identifiers::{ActorId, FromId, MsgId};
 use anyhow::{anyhow, Result};
-use std::fmt::Debug;
-use tokio::sync::mpsc::{channel, Receiver, Sender};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::{
+    broadcast,
+    mpsc::{channel, unbounded_channel, Receiver, Sender, UnboundedReceiver, UnboundedSender},
+    oneshot,
+};
+use tokio::time::Instant;
+use tracing::{debug_span, Span};
 
-pub struct Bus<A>
+/// The default number of messages the bus inbound channel can hold.
+pub const DEFAULT_BUS_CAPACITY: usize = 10;
+
+/// The maximum number of undeliverable messages the bus keeps around.
+/// It bounds as well the events a slow dead-letter subscriber can lag.
+const MAX_DEAD_LETTERS: usize = 100;
+
+pub struct Bus<A, P>
 where
     A: ActorId + Debug,
 {
-    ctrl_tx: Sender<CtrlMessage>,
-    actors_tx: Sender<Message<A>>,
+    ctrl_tx: Sender<CtrlMessage<A, P>>,
+    actors_tx: Sender<Message<A, P>>,
+    next_mid: Arc<AtomicU64>,
+    metrics: Arc<dyn Metrics<A>>,
+    dead_tx: broadcast::Sender<DeadLetterEvent<A>>,
+    undelivered_tx: UnboundedSender<Undelivered<A, P>>,
 }
 
-impl<A> Bus<A>
+impl<A, P> Clone for Bus<A, P>
 where
     A: ActorId + Debug,
 {
-    pub fn bus_tx(&self) -> Sender<Message<A>> {
+    fn clone(&self) -> Self {
+        Self {
+            ctrl_tx: self.ctrl_tx.clone(),
+            actors_tx: self.actors_tx.clone(),
+            next_mid: self.next_mid.clone(),
+            metrics: self.metrics.clone(),
+            dead_tx: self.dead_tx.clone(),
+            undelivered_tx: self.undelivered_tx.clone(),
+        }
+    }
+}
+
+impl<A, P> Bus<A, P>
+where
+    A: ActorId + Debug,
+{
+    pub fn bus_tx(&self) -> Sender<Message<A, P>> {
         self.actors_tx.clone()
     }
 
+    /// The metrics the bus, and the actors spawned on it, report to.
+    pub fn metrics(&self) -> Arc<dyn Metrics<A>> {
+        self.metrics.clone()
+    }
+
+    async fn send_ctrl(&self, ctrl_msg: CtrlMessage<A, P>) -> Result<()> {
+        self.ctrl_tx
+            .send(ctrl_msg)
+            .await
+            .map_err(|_e| anyhow!("Failed to send the control message"))
+    }
+
+    /// Reports the status of every registered actor and their aggregated health.
+    pub async fn query_health(&mut self) -> Result<HealthResponse<A>> {
+        let (health_tx, health_rx) = oneshot::channel::<HealthResponse<A>>();
+
+        self.send_ctrl(CtrlMessage::Health(health_tx))
+            .await
+            .unwrap();
+
+        health_rx
+            .await
+            .map_err(|e| anyhow!("Failed to receive the health of the bus [{e}]"))
+    }
+
+    pub async fn stop(&mut self) -> Result<()> {
+        let (quit_tx, quit_rx) = oneshot::channel::<QuitResponse>();
+        self.send_ctrl(CtrlMessage::Quit(quit_tx)).await.unwrap();
+        quit_rx
+            .await
+            .map(|_| ())
+            .map_err(|e| anyhow!("Failed to get the quit response [{e}]"))
+    }
+
+    /// Registers the mailbox of an actor, so the bus can route to it
+    /// all the messages which have the actor as destination.
+    /// Registering the same actor id twice replaces the previous mailbox.
+    pub async fn add_actor(&mut self, aid: A, tx: MailboxSender<A, P>) -> Result<()> {
+        let (add_tx, add_rx) = oneshot::channel::<AddActorResponse>();
+        self.send_ctrl(CtrlMessage::AddActor(aid, tx, add_tx))
+            .await?;
+        add_rx
+            .await
+            .map(|_| ())
+            .map_err(|e| anyhow!("Failed to get the add actor response [{e}]"))
+    }
+
+    /// Subscribes the actor to the topic, so it receives all the messages
+    /// published to it. Subscribing the same actor twice has no effect.
+    pub async fn subscribe<T>(&self, topic: T, aid: A) -> Result<()>
+    where
+        T: Into<String>,
+    {
+        self.send_ctrl(CtrlMessage::Subscribe(topic.into(), aid))
+            .await
+    }
+
+    /// Unsubscribes the actor from the topic.
+    pub async fn unsubscribe<T>(&self, topic: T, aid: A) -> Result<()>
+    where
+        T: Into<String>,
+    {
+        self.send_ctrl(CtrlMessage::Unsubscribe(topic.into(), aid))
+            .await
+    }
+
+    async fn recipients(&self, topic: Option<String>) -> Result<Vec<A>> {
+        let (recipients_tx, recipients_rx) = oneshot::channel::<Vec<A>>();
+        self.send_ctrl(CtrlMessage::Recipients(topic, recipients_tx))
+            .await?;
+        recipients_rx
+            .await
+            .map_err(|e| anyhow!("Failed to receive the recipients [{e}]"))
+    }
+
+    /// Marks the actor as restarting, until it is registered again.
+    pub(crate) async fn restarting(&mut self, aid: A) -> Result<()> {
+        self.send_ctrl(CtrlMessage::Restarting(aid)).await
+    }
+
+    /// Forgets the actor which stopped, its mailbox and its subscriptions,
+    /// unless it was registered again meanwhile.
+    pub(crate) async fn stopped(&self, aid: A) -> Result<()> {
+        self.send_ctrl(CtrlMessage::Stopped(aid)).await
+    }
+
+    /// The messages the bus could not deliver, oldest first, each with the
+    /// reason it was not delivered. The bus keeps only the last
+    /// undeliverable messages, see [`Bus::subscribe_dead_letters`] to
+    /// observe all of them.
+    pub async fn dead_letters(&mut self) -> Result<Vec<DeadLetter<A, P>>>
+    where
+        A: Clone,
+        P: Clone,
+    {
+        let (dead_tx, dead_rx) = oneshot::channel::<Vec<DeadLetter<A, P>>>();
+        self.send_ctrl(CtrlMessage::DeadLetters(DeadLetter::clone, dead_tx))
+            .await?;
+        dead_rx
+            .await
+            .map_err(|e| anyhow!("Failed to receive the dead letters [{e}]"))
+    }
+
+    /// Takes the messages the bus could not deliver, like
+    /// [`Bus::dead_letters`], leaving the dead-letter office empty.
+    pub async fn drain_dead_letters(&mut self) -> Result<Vec<DeadLetter<A, P>>> {
+        let (dead_tx, dead_rx) = oneshot::channel::<Vec<DeadLetter<A, P>>>();
+        self.send_ctrl(CtrlMessage::DrainDeadLetters(dead_tx))
+            .await?;
+        dead_rx
+            .await
+            .map_err(|e| anyhow!("Failed to receive the dead letters [{e}]"))
+    }
+
+    /// Hands to the dead-letter office a message which could not be
+    /// delivered past the bus, e.g. by a router to one of its workers.
+    /// A pending ask for the message fails right away.
+    pub(crate) fn dead_letter(&self, msg: Message<A, P>, reason: DeadLetterReason) {
+        // The bus is gone, so is its dead-letter office.
+        let _ = self.undelivered_tx.send((msg, reason));
+    }
+
+    /// Where the mailboxes hand the messages left when they are dropped.
+    pub(crate) fn undelivered_tx(&self) -> UnboundedSender<Undelivered<A, P>> {
+        self.undelivered_tx.clone()
+    }
+
+    /// Subscribes to the messages the bus could not deliver from now on.
+    /// A subscriber which falls behind misses the oldest events.
+    pub fn subscribe_dead_letters(&self) -> broadcast::Receiver<DeadLetterEvent<A>> {
+        self.dead_tx.subscribe()
+    }
+}
+
+impl<A, P> Bus<A, P>
+where
+    A: ActorId + Clone + Debug,
+    P: Clone,
+{
+    /// Sends a copy of the payload to every actor subscribed to the topic.
+    /// Returns the number of messages sent, which is zero when the topic
+    /// has no subscribers.
+    pub async fn publish<F, T>(&self, f: F, topic: T, payload: P) -> Result<usize>
+    where
+        F: Into<FromId<A>>,
+        T: Into<String>,
+    {
+        let recipients = self.recipients(Some(topic.into())).await?;
+        self.fan_out(f.into(), recipients, payload).await
+    }
+
+    /// Sends a copy of the payload to every registered actor, the sender
+    /// included if it is registered. Returns the number of messages sent.
+    pub async fn broadcast<F>(&self, f: F, payload: P) -> Result<usize>
+    where
+        F: Into<FromId<A>>,
+    {
+        let recipients = self.recipients(None).await?;
+        self.fan_out(f.into(), recipients, payload).await
+    }
+
+    async fn fan_out(&self, fid: FromId<A>, recipients: Vec<A>, payload: P) -> Result<usize> {
+        let count = recipients.len();
+        for tid in recipients {
+            let msg = Message::new(fid.clone(), tid, payload.clone());
+            self.actors_tx
+                .send(msg)
+                .await
+                .map_err(|_e| anyhow!("Failed to send the message to the bus"))?;
+        }
+        Ok(count)
+    }
+}
+
+impl<A, P> Bus<A, P>
+where
+    A: ActorId + Clone + Debug,
+{
+    /// Sends the message and waits for the destination actor to reply to it.
+    /// The message gets a fresh [`MsgId`], which the reply carries back as
+    /// its correlation id. Fails as soon as the bus cannot deliver the
+    /// message, e.g. the destination is not registered or its mailbox is
+    /// full and fails fast, or if the reply does not arrive before the timeout.
+    pub async fn ask(&self, msg: Message<A, P>, timeout: Duration) -> Result<Message<A, P>> {
+        let seq = self.next_mid.fetch_add(1, Ordering::Relaxed);
+        let mid = MsgId::from((msg.fid().as_ref().clone(), seq));
+
+        let (reply_tx, reply_rx) = oneshot::channel::<AskResponse<A, P>>();
+        self.send_ctrl(CtrlMessage::Ask(msg.with_mid(mid), reply_tx))
+            .await?;
+
+        match tokio::time::timeout(timeout, reply_rx).await {
+            Ok(Ok(Ok(reply))) => Ok(reply),
+            Ok(Ok(Err(reason))) => Err(anyhow!("Failed to deliver the ask [{reason}]")),
+            Ok(Err(_)) => Err(anyhow!("The destination of the ask is not reachable")),
+            Err(_) => Err(anyhow!("The ask timed out after {timeout:?}")),
+        }
+    }
+}
+
+impl<A, P> Bus<A, P>
+where
+    A: ActorId + Clone + Debug + Hash + Send + 'static,
+    P: Send + 'static,
+{
     async fn internal_loop(
-        mut ctrl_rx: Receiver<CtrlMessage>,
-        mut actors_rx: Receiver<Message<A>>,
+        mut ctrl_rx: Receiver<CtrlMessage<A, P>>,
+        mut actors_rx: Receiver<Message<A, P>>,
+        mut undelivered_rx: UnboundedReceiver<Undelivered<A, P>>,
+        undelivered_tx: UnboundedSender<Undelivered<A, P>>,
+        metrics: Arc<dyn Metrics<A>>,
+        dead_tx: broadcast::Sender<DeadLetterEvent<A>>,
     ) {
+        let mut table = RoutingTable::<A, P>::new(metrics, dead_tx, undelivered_tx);
+
         loop {
             tokio::select! {
+                // Messages already on the bus are routed before any control
+                // message, so a query observes everything sent before it.
+                biased;
+
+                Some(msg) = actors_rx.recv() => {
+                    route_span(&msg).in_scope(|| table.route(msg))
+                },
+                Some((msg, reason)) = undelivered_rx.recv() => table.undelivered(msg, reason),
                 msg = ctrl_rx.recv() =>
                     match msg {
                         Some(CtrlMessage::Quit(tx)) => {
@@ -33,58 +299,824 @@ where
                             break
                         },
                         Some(CtrlMessage::Health(tx)) => {
-                            tx.send(HealthResponse::Healty).unwrap();
+                            let _ = tx.send(table.health());
+                        },
+                        Some(CtrlMessage::AddActor(aid, actor_tx, tx)) => {
+                            table.actors.insert(aid, ActorEntry::new(actor_tx));
+                            let _ = tx.send(AddActorResponse {  });
+                        },
+                        Some(CtrlMessage::DeadLetters(copy, tx)) => {
+                            let _ = tx.send(table.dead_letters.iter().map(copy).collect());
+                        },
+                        Some(CtrlMessage::DrainDeadLetters(tx)) => {
+                            let _ = tx.send(table.dead_letters.drain(..).collect());
+                        },
+                        Some(CtrlMessage::Ask(msg, tx)) => {
+                            route_span(&msg).in_scope(|| table.ask(msg, tx))
+                        },
+                        Some(CtrlMessage::Subscribe(topic, aid)) => table.subscribe(topic, aid),
+                        Some(CtrlMessage::Unsubscribe(topic, aid)) => table.unsubscribe(&topic, &aid),
+                        Some(CtrlMessage::Recipients(topic, tx)) => {
+                            let _ = tx.send(table.recipients(topic.as_deref()));
+                        },
+                        Some(CtrlMessage::Restarting(aid)) => {
+                            if let Some(entry) = table.actors.get_mut(&aid) {
+                                entry.restarting = true;
+                            }
                         },
+                        Some(CtrlMessage::Stopped(aid)) => table.forget_closed(&aid),
                         None => break, // all senders have dropped
                     },
-                _msg = actors_rx.recv() => {
-                    todo!("Handler for the actors message not implemented yet!")
+            }
+        }
+    }
+}
+
+impl<A, P> Bus<A, P>
+where
+    A: ActorId + Clone + Debug + Hash + Send + 'static,
+    P: Send + 'static,
+{
+    pub async fn spawn() -> Result<Self> {
+        Self::spawn_with_capacity(DEFAULT_BUS_CAPACITY).await
+    }
+
+    /// Sends the message through the bus once the delay elapsed.
+    pub fn send_after(&self, msg: Message<A, P>, delay: Duration) -> TimerHandle {
+        timer::send_after(self.bus_tx(), msg, delay)
+    }
+
+    /// Sends a clone of the message through the bus every period. Fails
+    /// if the period is zero.
+    pub fn send_interval(&self, msg: Message<A, P>, period: Duration) -> Result<TimerHandle>
+    where
+        P: Clone,
+    {
+        timer::send_interval(self.bus_tx(), period, move || msg.clone())
+    }
+
+    /// Spawns the bus with an inbound channel which holds up to `capacity`
+    /// messages. When it is full the actors sending messages wait.
+    pub async fn spawn_with_capacity(capacity: usize) -> Result<Self> {
+        Self::spawn_with_metrics(capacity, Arc::new(NoopMetrics)).await
+    }
+
+    /// Spawns the bus reporting the message flow to the given metrics.
+    pub async fn spawn_with_metrics(capacity: usize, metrics: Arc<dyn Metrics<A>>) -> Result<Self> {
+        let (ctrl_tx, ctrl_rx) = channel::<CtrlMessage<A, P>>(10);
+        let (actors_tx, actors_rx) = channel::<Message<A, P>>(capacity);
+
+        let (dead_tx, _) = broadcast::channel::<DeadLetterEvent<A>>(MAX_DEAD_LETTERS);
+        let (undelivered_tx, undelivered_rx) = unbounded_channel::<Undelivered<A, P>>();
+
+        tokio::spawn(Self::internal_loop(
+            ctrl_rx,
+            actors_rx,
+            undelivered_rx,
+            undelivered_tx.clone(),
+            metrics.clone(),
+            dead_tx.clone(),
+        ));
+
+        Ok(Self {
+            ctrl_tx,
+            actors_tx,
+            next_mid: Arc::new(AtomicU64::new(0)),
+            metrics,
+            dead_tx,
+            undelivered_tx,
+        })
+    }
+}
+
+/// The span covering the routing of a message by the bus.
+fn route_span<A, P>(msg: &Message<A, P>) -> Span
+where
+    A: ActorId + Debug,
+{
+    debug_span!(
+        "route",
+        fid = ?msg.fid().as_ref(),
+        tid = ?msg.tid().as_ref(),
+        mid = ?msg.mid(),
+    )
+}
+
+/// A pending ask, waiting for the reply correlated with its message id.
+type PendingAsk<A, P> = (MsgId<A, u64>, oneshot::Sender<AskResponse<A, P>>);
+
+/// A message a backlog could not put in the mailbox, or which was not
+/// delivered past the bus, handed back to the bus.
+pub(crate) type Undelivered<A, P> = (Message<A, P>, DeadLetterReason);
+
+/// The messages waiting for room in a full mailbox whose policy is
+/// [`OverflowPolicy::Block`]. A task of its own waits for the room and
+/// puts them in the mailbox in order, so the bus keeps routing meanwhile.
+struct Backlog<A, P>
+where
+    A: ActorId + Debug,
+{
+    parked_tx: UnboundedSender<Message<A, P>>,
+    /// The messages parked and not yet in the mailbox.
+    waiting: Arc<AtomicUsize>,
+}
+
+impl<A, P> Backlog<A, P>
+where
+    A: ActorId + Clone + Debug + Send + 'static,
+    P: Send + 'static,
+{
+    fn spawn(
+        tx: MailboxSender<A, P>,
+        metrics: Arc<dyn Metrics<A>>,
+        undelivered_tx: UnboundedSender<Undelivered<A, P>>,
+    ) -> Self {
+        let (parked_tx, mut parked_rx) = unbounded_channel::<Message<A, P>>();
+        let waiting = Arc::new(AtomicUsize::new(0));
+
+        let forwarded = waiting.clone();
+        tokio::spawn(async move {
+            // Ends once the bus forgets the actor and the backlog is empty.
+            while let Some(msg) = parked_rx.recv().await {
+                let tid = msg.tid().as_ref().clone();
+                let undelivered = match tx.send(msg).await {
+                    Ok(dropped) => {
+                        metrics.message_routed(&tid);
+                        metrics.mailbox_depth(&tid, tx.len());
+                        dropped.map(|msg| (msg, DeadLetterReason::Discarded))
+                    }
+                    Err(MailboxError::Closed(msg)) => Some((msg, DeadLetterReason::MailboxClosed)),
+                    Err(MailboxError::Full(msg)) => Some((msg, DeadLetterReason::MailboxFull)),
+                };
+                if let Some(undelivered) = undelivered {
+                    let _ = undelivered_tx.send(undelivered);
                 }
+                forwarded.fetch_sub(1, Ordering::AcqRel);
             }
+        });
+
+        Self { parked_tx, waiting }
+    }
+
+    /// Returns `true` while some messages wait for room, the next ones
+    /// must queue behind them.
+    fn is_waiting(&self) -> bool {
+        self.waiting.load(Ordering::Acquire) > 0
+    }
+
+    fn park(&self, msg: Message<A, P>) {
+        self.waiting.fetch_add(1, Ordering::AcqRel);
+        // The task runs as long as the backlog exists.
+        let _ = self.parked_tx.send(msg);
+    }
+}
+
+/// What the bus knows about a registered actor.
+struct ActorEntry<A, P>
+where
+    A: ActorId + Debug,
+{
+    tx: MailboxSender<A, P>,
+    restarting: bool,
+    last_message: Option<Instant>,
+    /// Created the first time the mailbox is full.
+    backlog: Option<Backlog<A, P>>,
+}
+
+impl<A, P> ActorEntry<A, P>
+where
+    A: ActorId + Debug,
+{
+    fn new(tx: MailboxSender<A, P>) -> Self {
+        Self {
+            tx,
+            restarting: false,
+            last_message: None,
+            backlog: None,
         }
     }
 
-    async fn send_ctrl(&mut self, ctrl_msg: CtrlMessage) -> Result<()> {
-        self.ctrl_tx
-            .send(ctrl_msg)
-            .await
-            .map_err(|_e| anyhow!("Failed to send the control message"))
+    fn health(&self, aid: A) -> ActorHealth<A> {
+        let status = if self.restarting {
+            ActorStatus::Restarting
+        } else if self.tx.is_closed() {
+            ActorStatus::Stopped
+        } else {
+            ActorStatus::Running
+        };
+
+        ActorHealth {
+            aid,
+            status,
+            mailbox_depth: self.tx.len(),
+            mailbox_capacity: self.tx.config().capacity(),
+            last_message: self.last_message,
+        }
     }
+}
+
+/// The routing state owned by the bus task.
+struct RoutingTable<A, P>
+where
+    A: ActorId + Debug,
+{
+    actors: HashMap<A, ActorEntry<A, P>>,
+    asks: Vec<PendingAsk<A, P>>,
+    topics: HashMap<String, Vec<A>>,
+    dead_letters: VecDeque<DeadLetter<A, P>>,
+    dead_tx: broadcast::Sender<DeadLetterEvent<A>>,
+    metrics: Arc<dyn Metrics<A>>,
+    undelivered_tx: UnboundedSender<Undelivered<A, P>>,
+}
 
-    pub async fn query_health(&mut self) -> Result<HealthResponse> {
-        let (health_tx, health_rx) = tokio::sync::oneshot::channel::<HealthResponse>();
+impl<A, P> RoutingTable<A, P>
+where
+    A: ActorId + Clone + Debug + Hash + Send + 'static,
+    P: Send + 'static,
+{
+    fn new(
+        metrics: Arc<dyn Metrics<A>>,
+        dead_tx: broadcast::Sender<DeadLetterEvent<A>>,
+        undelivered_tx: UnboundedSender<Undelivered<A, P>>,
+    ) -> Self {
+        Self {
+            actors: HashMap::new(),
+            asks: Vec::new(),
+            topics: HashMap::new(),
+            dead_letters: VecDeque::new(),
+            dead_tx,
+            metrics,
+            undelivered_tx,
+        }
+    }
 
-        self.send_ctrl(CtrlMessage::Health(health_tx))
+    fn subscribe(&mut self, topic: String, aid: A) {
+        let subscribers = self.topics.entry(topic).or_default();
+        if !subscribers.contains(&aid) {
+            subscribers.push(aid);
+        }
+    }
+
+    fn unsubscribe(&mut self, topic: &str, aid: &A) {
+        if let Some(subscribers) = self.topics.get_mut(topic) {
+            subscribers.retain(|subscriber| subscriber != aid);
+            if subscribers.is_empty() {
+                self.topics.remove(topic);
+            }
+        }
+    }
+
+    /// The registered actors subscribed to the topic, in order of
+    /// subscription, or all the registered actors when there is no topic.
+    fn recipients(&self, topic: Option<&str>) -> Vec<A> {
+        match topic {
+            Some(topic) => self
+                .topics
+                .get(topic)
+                .map(|subscribers| {
+                    subscribers
+                        .iter()
+                        .filter(|aid| self.actors.contains_key(aid))
+                        .cloned()
+                        .collect()
+                })
+                .unwrap_or_default(),
+            None => self.actors.keys().cloned().collect(),
+        }
+    }
+
+    fn health(&self) -> HealthResponse<A> {
+        let actors = self
+            .actors
+            .iter()
+            .map(|(aid, entry)| entry.health(aid.clone()))
+            .collect();
+        HealthResponse::new(actors)
+    }
+
+    /// Hands the message to the pending ask it replies to, if there
+    /// is one, otherwise to the actor identified by its destination.
+    fn route(&mut self, msg: Message<A, P>) {
+        let Some(msg) = self.complete_ask(msg) else {
+            return;
+        };
+
+        if let Some((msg, reason)) = self.deliver(msg) {
+            self.dead_letter(msg, reason);
+        }
+    }
+
+    /// Registers the pending ask and delivers its message.
+    fn ask(&mut self, msg: Message<A, P>, reply_tx: oneshot::Sender<AskResponse<A, P>>) {
+        // Forget the asks abandoned by their callers, e.g. timed out.
+        self.asks.retain(|(_, tx)| !tx.is_closed());
+
+        if let Some(mid) = msg.mid() {
+            self.asks.push((mid.clone(), reply_tx));
+        }
+
+        if let Some((msg, reason)) = self.deliver(msg) {
+            self.dead_letter(msg, reason);
+        }
+    }
+
+    /// Returns the message back if it is not a reply to a pending ask.
+    fn complete_ask(&mut self, msg: Message<A, P>) -> Option<Message<A, P>> {
+        let Some(cid) = msg.cid() else {
+            return Some(msg);
+        };
+        let Some(i) = self.asks.iter().position(|(mid, _)| mid == cid) else {
+            return Some(msg);
+        };
+
+        let (_, reply_tx) = self.asks.swap_remove(i);
+        reply_tx.send(Ok(msg)).err().and_then(Result::ok)
+    }
+
+    /// Delivers the message to the actor identified by its destination,
+    /// without ever waiting: when the mailbox is full and its policy is
+    /// to block, the message waits in the backlog of the actor instead.
+    /// Returns back the message which could not be delivered, either the
+    /// given one or the one discarded by the mailbox to make room for it.
+    fn deliver(&mut self, msg: Message<A, P>) -> Option<(Message<A, P>, DeadLetterReason)> {
+        let Some(entry) = self.actors.get_mut(msg.tid().as_ref()) else {
+            return Some((msg, DeadLetterReason::UnknownActor));
+        };
+
+        let tid = msg.tid().as_ref().clone();
+        if let Some(backlog) = entry
+            .backlog
+            .as_ref()
+            .filter(|backlog| backlog.is_waiting())
+        {
+            // Behind the messages already waiting, to keep the order.
+            backlog.park(msg);
+            entry.last_message = Some(Instant::now());
+            return None;
+        }
+
+        match entry.tx.try_send(msg) {
+            Ok(dropped) => {
+                entry.last_message = Some(Instant::now());
+                self.metrics.message_routed(&tid);
+                self.metrics.mailbox_depth(&tid, entry.tx.len());
+                dropped.map(|msg| (msg, DeadLetterReason::Discarded))
+            }
+            Err(MailboxError::Closed(msg)) => {
+                self.forget(&tid);
+                Some((msg, DeadLetterReason::MailboxClosed))
+            }
+            Err(MailboxError::Full(msg))
+                if entry.tx.config().overflow() == OverflowPolicy::Block =>
+            {
+                let backlog = entry.backlog.get_or_insert_with(|| {
+                    Backlog::spawn(
+                        entry.tx.clone(),
+                        self.metrics.clone(),
+                        self.undelivered_tx.clone(),
+                    )
+                });
+                backlog.park(msg);
+                entry.last_message = Some(Instant::now());
+                None
+            }
+            Err(MailboxError::Full(msg)) => Some((msg, DeadLetterReason::MailboxFull)),
+        }
+    }
+
+    /// Handles a message a backlog could not put in the mailbox, or which
+    /// was not delivered past the bus.
+    fn undelivered(&mut self, msg: Message<A, P>, reason: DeadLetterReason) {
+        let tid = msg.tid().as_ref().clone();
+        self.forget_closed(&tid);
+        self.dead_letter(msg, reason);
+    }
+
+    /// Forgets the actor if its mailbox is closed. The actor may have been
+    /// registered again since, with a new mailbox, which is kept.
+    fn forget_closed(&mut self, aid: &A) {
+        if self
+            .actors
+            .get(aid)
+            .is_some_and(|entry| entry.tx.is_closed())
+        {
+            self.forget(aid);
+        }
+    }
+
+    /// The actor is gone, there is no point to keep its mailbox nor its
+    /// subscriptions.
+    fn forget(&mut self, aid: &A) {
+        self.actors.remove(aid);
+        self.topics.retain(|_, subscribers| {
+            subscribers.retain(|subscriber| subscriber != aid);
+            !subscribers.is_empty()
+        });
+    }
+
+    fn dead_letter(&mut self, msg: Message<A, P>, reason: DeadLetterReason) {
+        tracing::debug!(tid = ?msg.tid().as_ref(), %reason, "dead letter");
+        // Nobody is going to reply, so fail the ask right away.
+        if let Some(mid) = msg.mid() {
+            if let Some(i) = self.asks.iter().position(|(pending, _)| pending == mid) {
+                let (_, reply_tx) = self.asks.swap_remove(i);
+                let _ = reply_tx.send(Err(reason));
+            }
+        }
+
+        self.metrics.message_dead(msg.tid().as_ref());
+
+        let dead_letter = DeadLetter::new(msg, reason);
+        // Nobody may be subscribed, the dead letter is kept anyway.
+        let _ = self.dead_tx.send(dead_letter.event());
+
+        if self.dead_letters.len() == MAX_DEAD_LETTERS {
+            self.dead_letters.pop_front();
+        }
+        self.dead_letters.push_back(dead_letter);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mailbox, Health, MailboxConfig, OverflowPolicy};
+
+    #[tokio::test]
+    async fn route_to_actor() {
+        let mut bus = Bus::<u8, &str>::spawn().await.unwrap();
+
+        let (tx, mut rx) = mailbox::<u8, &str>(MailboxConfig::bounded(10));
+        bus.add_actor(2, tx).await.unwrap();
+
+        bus.bus_tx().send(Message::new(1, 2, "ping")).await.unwrap();
+
+        let msg = rx.recv().await.unwrap();
+        assert_eq!(msg.fid().as_ref(), &1);
+        assert_eq!(msg.tid().as_ref(), &2);
+        assert_eq!(msg.payload(), &"ping");
+
+        bus.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn publish_to_topic() {
+        let mut bus = Bus::<u8, &str>::spawn().await.unwrap();
+
+        let (tx2, mut rx2) = mailbox::<u8, &str>(MailboxConfig::bounded(10));
+        let (tx3, mut rx3) = mailbox::<u8, &str>(MailboxConfig::bounded(10));
+        bus.add_actor(2, tx2.clone()).await.unwrap();
+        bus.add_actor(3, tx3).await.unwrap();
+
+        bus.subscribe("orders", 2).await.unwrap();
+        bus.subscribe("orders", 2).await.unwrap();
+        bus.subscribe("orders", 3).await.unwrap();
+        // actors which are not registered do not receive anything.
+        bus.subscribe("orders", 4).await.unwrap();
+
+        assert_eq!(bus.publish(1, "orders", "new").await.unwrap(), 2);
+        assert_eq!(bus.publish(1, "payments", "new").await.unwrap(), 0);
+
+        for rx in [&mut rx2, &mut rx3] {
+            let msg = rx.recv().await.unwrap();
+            assert_eq!(msg.fid().as_ref(), &1);
+            assert_eq!(msg.payload(), &"new");
+        }
+
+        bus.unsubscribe("orders", 2).await.unwrap();
+        assert_eq!(bus.publish(1, "orders", "update").await.unwrap(), 1);
+        assert_eq!(rx3.recv().await.unwrap().payload(), &"update");
+        assert!(tx2.is_empty());
+
+        // a stopped actor loses its subscriptions.
+        drop(rx3);
+        assert_eq!(bus.publish(1, "orders", "update").await.unwrap(), 1);
+        assert_eq!(bus.publish(1, "orders", "update").await.unwrap(), 0);
+
+        bus.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn broadcast() {
+        let mut bus = Bus::<u8, &str>::spawn().await.unwrap();
+
+        let mut receivers = Vec::new();
+        for aid in 2..5 {
+            let (tx, rx) = mailbox::<u8, &str>(MailboxConfig::bounded(10));
+            bus.add_actor(aid, tx).await.unwrap();
+            receivers.push((aid, rx));
+        }
+
+        assert_eq!(bus.broadcast(1, "hello").await.unwrap(), 3);
+
+        for (aid, rx) in receivers.iter_mut() {
+            let msg = rx.recv().await.unwrap();
+            assert_eq!(msg.tid().as_ref(), aid);
+            assert_eq!(msg.payload(), &"hello");
+        }
+
+        bus.stop().await.unwrap();
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        routed: std::sync::Mutex<Vec<u8>>,
+        dead: std::sync::Mutex<Vec<u8>>,
+        depths: std::sync::Mutex<Vec<(u8, usize)>>,
+    }
+
+    impl Metrics<u8> for Recorder {
+        fn message_routed(&self, tid: &u8) {
+            self.routed.lock().unwrap().push(*tid);
+        }
+
+        fn message_dead(&self, tid: &u8) {
+            self.dead.lock().unwrap().push(*tid);
+        }
+
+        fn mailbox_depth(&self, aid: &u8, depth: usize) {
+            self.depths.lock().unwrap().push((*aid, depth));
+        }
+    }
+
+    #[tokio::test]
+    async fn metrics() {
+        let recorder = Arc::new(Recorder::default());
+        let mut bus = Bus::<u8, &str>::spawn_with_metrics(10, recorder.clone())
             .await
             .unwrap();
 
-        health_rx
+        let (tx, _rx) = mailbox::<u8, &str>(MailboxConfig::bounded(10));
+        bus.add_actor(2, tx).await.unwrap();
+
+        bus.bus_tx().send(Message::new(1, 2, "one")).await.unwrap();
+        bus.bus_tx().send(Message::new(1, 2, "two")).await.unwrap();
+        bus.bus_tx().send(Message::new(1, 3, "lost")).await.unwrap();
+        bus.dead_letters().await.unwrap();
+
+        assert_eq!(*recorder.routed.lock().unwrap(), vec![2, 2]);
+        assert_eq!(*recorder.depths.lock().unwrap(), vec![(2, 1), (2, 2)]);
+        assert_eq!(*recorder.dead.lock().unwrap(), vec![3]);
+
+        bus.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn route_to_unknown_actor() {
+        let mut bus = Bus::<u8, &str>::spawn().await.unwrap();
+
+        bus.bus_tx().send(Message::new(1, 2, "ping")).await.unwrap();
+
+        let dead_letters = bus.dead_letters().await.unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].msg.tid().as_ref(), &2);
+        assert_eq!(dead_letters[0].reason, DeadLetterReason::UnknownActor);
+
+        // reading the dead letters leaves them in the office.
+        assert_eq!(bus.dead_letters().await.unwrap().len(), 1);
+        assert_eq!(bus.drain_dead_letters().await.unwrap().len(), 1);
+        assert!(bus.dead_letters().await.unwrap().is_empty());
+
+        bus.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn route_to_closed_actor() {
+        let mut bus = Bus::<u8, &str>::spawn().await.unwrap();
+
+        let (tx, rx) = mailbox::<u8, &str>(MailboxConfig::bounded(10));
+        bus.add_actor(2, tx).await.unwrap();
+        drop(rx);
+
+        bus.bus_tx().send(Message::new(1, 2, "ping")).await.unwrap();
+        bus.bus_tx().send(Message::new(1, 2, "ping")).await.unwrap();
+
+        let dead_letters = bus.dead_letters().await.unwrap();
+        assert_eq!(dead_letters.len(), 2);
+        // the closed mailbox is forgotten after the first failed delivery.
+        assert_eq!(dead_letters[0].reason, DeadLetterReason::MailboxClosed);
+        assert_eq!(dead_letters[1].reason, DeadLetterReason::UnknownActor);
+
+        bus.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn subscribe_dead_letters() {
+        let mut bus = Bus::<u8, &str>::spawn().await.unwrap();
+        let mut dead_rx = bus.subscribe_dead_letters();
+
+        let config = MailboxConfig::bounded(1).with_overflow(OverflowPolicy::DropOldest);
+        let (tx, _rx) = mailbox::<u8, &str>(config);
+        bus.add_actor(2, tx).await.unwrap();
+
+        bus.bus_tx().send(Message::new(1, 2, "old")).await.unwrap();
+        bus.bus_tx().send(Message::new(1, 2, "new")).await.unwrap();
+        bus.bus_tx().send(Message::new(1, 3, "lost")).await.unwrap();
+
+        let event = dead_rx.recv().await.unwrap();
+        assert_eq!((event.fid, event.tid), (1, 2));
+        assert_eq!(event.reason, DeadLetterReason::Discarded);
+        let event = dead_rx.recv().await.unwrap();
+        assert_eq!(event.tid, 3);
+        assert_eq!(event.reason, DeadLetterReason::UnknownActor);
+
+        // the office still holds the messages themselves.
+        let payloads = bus
+            .dead_letters()
             .await
-            .map_err(|e| anyhow!("Failed to receive the health of the bus [{e}]"))
+            .unwrap()
+            .into_iter()
+            .map(|dead_letter| dead_letter.msg.into_payload())
+            .collect::<Vec<_>>();
+        assert_eq!(payloads, vec!["old", "lost"]);
+
+        bus.stop().await.unwrap();
     }
 
-    pub async fn stop(&mut self) -> Result<()> {
-        let (quit_tx, quit_rx) = tokio::sync::oneshot::channel::<QuitResponse>();
-        self.send_ctrl(CtrlMessage::Quit(quit_tx)).await.unwrap();
-        quit_rx
+    #[tokio::test]
+    async fn ask_and_reply() {
+        let mut bus = Bus::<u8, &str>::spawn().await.unwrap();
+
+        let (tx, mut rx) = mailbox::<u8, &str>(MailboxConfig::bounded(10));
+        bus.add_actor(2, tx).await.unwrap();
+
+        let replier = bus.bus_tx();
+        tokio::spawn(async move {
+            let msg = rx.recv().await.unwrap();
+            let reply = Message::new(2, 1, "pong").with_cid(msg.mid().unwrap().clone());
+            replier.send(reply).await.unwrap();
+        });
+
+        let reply = bus
+            .ask(Message::new(1, 2, "ping"), Duration::from_secs(1))
             .await
-            .map(|_| ())
-            .map_err(|e| anyhow!("Failed to get the quit response [{e}]"))
+            .unwrap();
+        assert_eq!(reply.payload(), &"pong");
+
+        bus.stop().await.unwrap();
     }
 
-    pub fn add_actor(&mut self, _aid: A, _tx: Sender<Message<A>>) {}
-}
+    #[tokio::test]
+    async fn ask_unknown_actor() {
+        let mut bus = Bus::<u8, &str>::spawn().await.unwrap();
 
-impl<A> Bus<A>
-where
-    A: ActorId + Debug + Send + 'static,
-{
-    pub async fn spawn() -> Result<Self> {
-        let (ctrl_tx, ctrl_rx) = channel::<CtrlMessage>(10);
-        let (actors_tx, actors_rx) = channel::<Message<A>>(10);
+        let res = bus
+            .ask(Message::new(1, 2, "ping"), Duration::from_secs(1))
+            .await;
+        assert!(res.is_err());
 
-        tokio::spawn(Self::internal_loop(ctrl_rx, actors_rx));
+        let dead_letters = bus.dead_letters().await.unwrap();
+        assert_eq!(dead_letters.len(), 1);
+
+        bus.stop().await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ask_timeout() {
+        let mut bus = Bus::<u8, &str>::spawn().await.unwrap();
+
+        // the actor never replies.
+        let (tx, _rx) = mailbox::<u8, &str>(MailboxConfig::bounded(10));
+        bus.add_actor(2, tx).await.unwrap();
+
+        let res = bus
+            .ask(Message::new(1, 2, "ping"), Duration::from_secs(1))
+            .await;
+        assert!(res.is_err());
+
+        bus.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn query_health() {
+        let mut bus = Bus::<u8, &str>::spawn().await.unwrap();
+
+        let health = bus.query_health().await.unwrap();
+        assert_eq!(health.health, Health::Healthy);
+        assert!(health.actors.is_empty());
+
+        let (tx1, _rx1) = mailbox::<u8, &str>(MailboxConfig::bounded(2));
+        bus.add_actor(1, tx1).await.unwrap();
+        let (tx2, rx2) = mailbox::<u8, &str>(MailboxConfig::bounded(2));
+        bus.add_actor(2, tx2).await.unwrap();
+
+        bus.bus_tx().send(Message::new(0, 1, "ping")).await.unwrap();
+        drop(rx2);
+
+        let health = bus.query_health().await.unwrap();
+        assert_eq!(health.health, Health::Degraded);
+
+        let actor = health.actor(&1).unwrap();
+        assert_eq!(actor.status, ActorStatus::Running);
+        assert_eq!(actor.mailbox_depth, 1);
+        assert_eq!(actor.mailbox_capacity, Some(2));
+        assert!(actor.last_message.is_some());
+
+        let actor = health.actor(&2).unwrap();
+        assert_eq!(actor.status, ActorStatus::Stopped);
+        assert!(actor.last_message.is_none());
+
+        bus.restarting(1).await.unwrap();
+        let health = bus.query_health().await.unwrap();
+        assert_eq!(health.health, Health::Unhealthy);
+        assert!(!health.is_ready());
+
+        bus.stop().await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn route_to_full_actor() {
+        let mut bus = Bus::<u8, &str>::spawn().await.unwrap();
+
+        let config = MailboxConfig::bounded(1).with_overflow(OverflowPolicy::FailFast);
+        let (tx, mut rx) = mailbox::<u8, &str>(config);
+        bus.add_actor(2, tx).await.unwrap();
+
+        bus.bus_tx()
+            .send(Message::new(1, 2, "first"))
+            .await
+            .unwrap();
+        bus.bus_tx()
+            .send(Message::new(1, 2, "second"))
+            .await
+            .unwrap();
+
+        // the ask fails right away, long before its timeout.
+        let start = Instant::now();
+        let err = bus
+            .ask(Message::new(1, 2, "third"), Duration::from_secs(10))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("mailbox full"), "{err}");
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        let dead_letters = bus.dead_letters().await.unwrap();
+        assert_eq!(dead_letters.len(), 2);
+        assert_eq!(dead_letters[0].msg.payload(), &"second");
+        assert_eq!(dead_letters[0].reason, DeadLetterReason::MailboxFull);
+        assert_eq!(dead_letters[1].msg.payload(), &"third");
+        assert_eq!(rx.recv().await.unwrap().payload(), &"first");
+
+        bus.stop().await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn route_around_blocked_actor() {
+        let recorder = Arc::new(Recorder::default());
+        let mut bus = Bus::<u8, u32>::spawn_with_metrics(1, recorder.clone())
+            .await
+            .unwrap();
+
+        // actor 2 does not receive anything until the end.
+        let (tx2, mut rx2) = mailbox::<u8, u32>(MailboxConfig::bounded(1));
+        bus.add_actor(2, tx2).await.unwrap();
+        let (tx3, mut rx3) = mailbox::<u8, u32>(MailboxConfig::bounded(1));
+        bus.add_actor(3, tx3).await.unwrap();
+
+        for payload in 0..4 {
+            bus.bus_tx()
+                .send(Message::new(1, 2, payload))
+                .await
+                .unwrap();
+        }
+
+        // neither the other actors nor the control messages wait for actor 2.
+        let start = Instant::now();
+        bus.bus_tx().send(Message::new(1, 3, 10)).await.unwrap();
+        assert_eq!(rx3.recv().await.unwrap().into_payload(), 10);
+        let health = bus.query_health().await.unwrap();
+        assert_eq!(health.actor(&2).unwrap().mailbox_depth, 1);
+        assert!(bus.dead_letters().await.unwrap().is_empty());
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        // the messages waiting for room arrive in order.
+        let mut payloads = Vec::new();
+        for _ in 0..4 {
+            payloads.push(rx2.recv().await.unwrap().into_payload());
+        }
+        assert_eq!(payloads, vec![0, 1, 2, 3]);
+
+        // once the backlog is empty, the messages go straight to the mailbox.
+        bus.bus_tx().send(Message::new(1, 2, 4)).await.unwrap();
+        assert_eq!(rx2.recv().await.unwrap().into_payload(), 4);
+        assert_eq!(*recorder.routed.lock().unwrap(), vec![2, 3, 2, 2, 2, 2]);
+
+        // the messages still waiting when the actor stops are dead letters.
+        for payload in 5..8 {
+            bus.bus_tx()
+                .send(Message::new(1, 2, payload))
+                .await
+                .unwrap();
+        }
+        let mut dead_rx = bus.subscribe_dead_letters();
+        bus.query_health().await.unwrap();
+        drop(rx2);
+        for _ in 6..8 {
+            let event = dead_rx.recv().await.unwrap();
+            assert_eq!(event.reason, DeadLetterReason::MailboxClosed);
+        }
+        assert!(bus.query_health().await.unwrap().actor(&2).is_none());
 
-        Ok(Self { ctrl_tx, actors_tx })
+        bus.stop().await.unwrap();
     }
 }
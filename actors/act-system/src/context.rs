@@ -0,0 +1,228 @@
+use crate::{timer, Message, TimerHandle};
+use act_identifiers::{ActorId, ToId};
+use anyhow::Result;
+use std::{fmt::Debug, time::Duration};
+use tokio::{sync::mpsc::Sender, task::AbortHandle};
+
+/// The Context gives a handler access to the actor running it. The
+/// messages sent through the context are collected and forwarded to
+/// the bus by the actor task once the handler returns. The scheduled
+/// messages are sent to the bus directly by their timers, which the
+/// context cancels when the actor stops, so a restarted actor does not
+/// inherit the timers of the previous one.
+pub struct Context<A, P>
+where
+    A: ActorId + Debug,
+{
+    aid: A,
+    outbox: Vec<Message<A, P>>,
+    stopping: bool,
+    bus_tx: Sender<Message<A, P>>,
+    timers: Vec<AbortHandle>,
+}
+
+impl<A, P> Context<A, P>
+where
+    A: ActorId + Debug,
+{
+    pub(crate) fn new(aid: A, bus_tx: Sender<Message<A, P>>) -> Self {
+        Self {
+            aid,
+            outbox: Vec::new(),
+            stopping: false,
+            bus_tx,
+            timers: Vec::new(),
+        }
+    }
+
+    /// The identifier of the actor running the handler.
+    pub fn aid(&self) -> &A {
+        &self.aid
+    }
+
+    /// Asks the actor to stop once the current message is handled.
+    pub fn stop(&mut self) {
+        self.stopping = true;
+    }
+
+    pub fn is_stopping(&self) -> bool {
+        self.stopping
+    }
+
+    pub(crate) fn drain(&mut self) -> std::vec::Drain<'_, Message<A, P>> {
+        self.outbox.drain(..)
+    }
+
+    /// Keeps the timer, to cancel it when the actor stops.
+    fn track(&mut self, timer: TimerHandle) -> TimerHandle {
+        self.timers.retain(|timer| !timer.is_finished());
+        self.timers.push(timer.abort_handle());
+        timer
+    }
+}
+
+impl<A, P> Drop for Context<A, P>
+where
+    A: ActorId + Debug,
+{
+    fn drop(&mut self) {
+        for timer in self.timers.drain(..) {
+            timer.abort();
+        }
+    }
+}
+
+impl<A, P> Context<A, P>
+where
+    A: ActorId + Clone + Debug,
+{
+    /// Sends the payload to the given actor.
+    pub fn send<T>(&mut self, t: T, payload: P)
+    where
+        T: Into<ToId<A>>,
+    {
+        let msg = Message::new(self.aid.clone(), t, payload);
+        self.outbox.push(msg);
+    }
+
+    /// Sends the payload back to the actor which sent the given message.
+    /// The reply is correlated with the given message, so it completes
+    /// the pending `ask` which sent it, if there is one.
+    pub fn reply(&mut self, msg: &Message<A, P>, payload: P) {
+        let tid: ToId<A> = msg.fid().clone().into();
+        let mut reply = Message::new(self.aid.clone(), tid, payload);
+        if let Some(mid) = msg.mid() {
+            reply = reply.with_cid(mid.clone());
+        }
+        self.outbox.push(reply);
+    }
+}
+
+impl<A, P> Context<A, P>
+where
+    A: ActorId + Clone + Debug + Send + 'static,
+    P: Send + 'static,
+{
+    /// Sends the payload to the given actor once the delay elapsed.
+    /// Use [`Context::aid`] as destination to schedule a message to self.
+    pub fn send_after<T>(&mut self, t: T, payload: P, delay: Duration) -> TimerHandle
+    where
+        T: Into<ToId<A>>,
+    {
+        let msg = Message::new(self.aid.clone(), t, payload);
+        let timer = timer::send_after(self.bus_tx.clone(), msg, delay);
+        self.track(timer)
+    }
+
+    /// Sends a clone of the payload to the given actor every period.
+    /// Fails if the period is zero.
+    pub fn send_interval<T>(&mut self, t: T, payload: P, period: Duration) -> Result<TimerHandle>
+    where
+        T: Into<ToId<A>>,
+        P: Clone,
+    {
+        let fid = self.aid.clone();
+        let tid: ToId<A> = t.into();
+        let timer = timer::send_interval(self.bus_tx.clone(), period, move || {
+            Message::new(fid.clone(), tid.clone(), payload.clone())
+        })?;
+        Ok(self.track(timer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use act_identifiers::MsgId;
+    use tokio::sync::mpsc::channel;
+
+    #[test]
+    fn send() {
+        let mut ctx = Context::<u8, &str>::new(1, channel(1).0);
+        ctx.send(2, "ping");
+
+        let msgs = ctx.drain().collect::<Vec<_>>();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].fid().as_ref(), &1);
+        assert_eq!(msgs[0].tid().as_ref(), &2);
+        assert_eq!(msgs[0].payload(), &"ping");
+    }
+
+    #[test]
+    fn reply() {
+        let mut ctx = Context::<u8, &str>::new(1, channel(1).0);
+        ctx.reply(&Message::new(2, 1, "ping"), "pong");
+
+        let msgs = ctx.drain().collect::<Vec<_>>();
+        assert_eq!(msgs[0].fid().as_ref(), &1);
+        assert_eq!(msgs[0].tid().as_ref(), &2);
+        assert_eq!(msgs[0].payload(), &"pong");
+        assert!(msgs[0].cid().is_none());
+        assert_eq!(ctx.drain().count(), 0);
+    }
+
+    #[test]
+    fn reply_correlated() {
+        let mut ctx = Context::<u8, &str>::new(1, channel(1).0);
+        let msg = Message::new(2, 1, "ping").with_mid(MsgId::from((2, 5)));
+        ctx.reply(&msg, "pong");
+
+        let msgs = ctx.drain().collect::<Vec<_>>();
+        assert_eq!(msgs[0].cid(), Some(&MsgId::from((2, 5))));
+    }
+
+    #[test]
+    fn stop() {
+        let mut ctx = Context::<u8, &str>::new(1, channel(1).0);
+        assert!(!ctx.is_stopping());
+        ctx.stop();
+        assert!(ctx.is_stopping());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn send_after() {
+        let (tx, mut rx) = channel::<Message<u8, &str>>(10);
+        let mut ctx = Context::<u8, &str>::new(1, tx);
+
+        let _timer = ctx.send_after(*ctx.aid(), "tick", Duration::from_secs(1));
+
+        let msg = rx.recv().await.unwrap();
+        assert_eq!(msg.fid().as_ref(), &1);
+        assert_eq!(msg.tid().as_ref(), &1);
+        assert_eq!(msg.payload(), &"tick");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn send_interval() {
+        let (tx, mut rx) = channel::<Message<u8, &str>>(10);
+        let mut ctx = Context::<u8, &str>::new(1, tx);
+
+        let timer = ctx
+            .send_interval(2, "tick", Duration::from_secs(1))
+            .unwrap();
+        for _ in 0..3 {
+            assert_eq!(rx.recv().await.unwrap().payload(), &"tick");
+        }
+        timer.cancel();
+
+        assert!(ctx.send_interval(2, "tick", Duration::ZERO).is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timers_end_with_context() {
+        let (tx, mut rx) = channel::<Message<u8, &str>>(10);
+        let mut ctx = Context::<u8, &str>::new(1, tx);
+
+        let interval = ctx
+            .send_interval(2, "tick", Duration::from_secs(1))
+            .unwrap();
+        let after = ctx.send_after(2, "later", Duration::from_secs(5));
+        assert_eq!(rx.recv().await.unwrap().payload(), &"tick");
+
+        // the actor stopped: its timers are cancelled.
+        drop(ctx);
+        assert!(rx.recv().await.is_none());
+        assert!(interval.is_finished());
+        assert!(after.is_finished());
+    }
+}
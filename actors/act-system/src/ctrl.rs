@@ -1,15 +1,38 @@
+use crate::{DeadLetter, DeadLetterReason, HealthResponse, MailboxSender, Message};
+use act_identifiers::ActorId;
+use std::fmt::Debug;
 use tokio::sync::oneshot::Sender;
 
 #[derive(Debug)]
-pub enum HealthResponse {
-    Healty,
-}
+pub struct QuitResponse {}
 
 #[derive(Debug)]
-pub struct QuitResponse {}
+pub struct AddActorResponse {}
+
+/// Copies a dead letter.
+pub type CopyFn<A, P> = fn(&DeadLetter<A, P>) -> DeadLetter<A, P>;
+
+/// The reply to an ask, or why the bus could not deliver the message asked.
+pub type AskResponse<A, P> = Result<Message<A, P>, DeadLetterReason>;
 
 #[derive(Debug)]
-pub enum CtrlMessage {
+pub enum CtrlMessage<A, P>
+where
+    A: ActorId + Debug,
+{
     Quit(Sender<QuitResponse>),
-    Health(Sender<HealthResponse>),
+    Health(Sender<HealthResponse<A>>),
+    Restarting(A),
+    /// The actor stopped, forget it unless it was registered again since.
+    Stopped(A),
+    AddActor(A, MailboxSender<A, P>, Sender<AddActorResponse>),
+    /// A copy of the dead-letter office, made with the given function so
+    /// the bus does not require the payloads to be clonable.
+    DeadLetters(CopyFn<A, P>, Sender<Vec<DeadLetter<A, P>>>),
+    DrainDeadLetters(Sender<Vec<DeadLetter<A, P>>>),
+    Ask(Message<A, P>, Sender<AskResponse<A, P>>),
+    Subscribe(String, A),
+    Unsubscribe(String, A),
+    /// The actors subscribed to the topic, or all the registered actors.
+    Recipients(Option<String>, Sender<Vec<A>>),
 }
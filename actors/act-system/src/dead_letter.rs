@@ -0,0 +1,104 @@
+use crate::Message;
+use act_identifiers::{ActorId, MsgId};
+use std::fmt::{Debug, Display};
+use tokio::time::Instant;
+
+/// Why the bus could not deliver a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeadLetterReason {
+    /// No actor is registered with the destination id.
+    UnknownActor,
+    /// The destination actor stopped, its mailbox is closed.
+    MailboxClosed,
+    /// The destination mailbox is full and its overflow policy fails fast.
+    MailboxFull,
+    /// The destination mailbox overflow policy discarded the message.
+    Discarded,
+    /// The destination actor stopped before handling the message, which
+    /// was left in its mailbox.
+    ActorStopped,
+    /// The message is too large to be sent to the remote node of its
+    /// destination, see [`MAX_FRAME_LEN`](crate::MAX_FRAME_LEN).
+    TooLarge,
+}
+
+impl Display for DeadLetterReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            DeadLetterReason::UnknownActor => "unknown actor",
+            DeadLetterReason::MailboxClosed => "mailbox closed",
+            DeadLetterReason::MailboxFull => "mailbox full",
+            DeadLetterReason::Discarded => "discarded",
+            DeadLetterReason::ActorStopped => "actor stopped",
+            DeadLetterReason::TooLarge => "too large",
+        };
+        write!(f, "{reason}")
+    }
+}
+
+/// A message the bus could not deliver, kept by the dead-letter office.
+#[derive(Debug, Clone)]
+pub struct DeadLetter<A, P>
+where
+    A: ActorId + Debug,
+{
+    pub msg: Message<A, P>,
+    pub reason: DeadLetterReason,
+    pub at: Instant,
+}
+
+/// The notice published to the dead-letter subscribers. It carries the
+/// identifiers of the undeliverable message but not its payload, which
+/// stays with the dead-letter office.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetterEvent<A>
+where
+    A: ActorId,
+{
+    pub fid: A,
+    pub tid: A,
+    pub mid: Option<MsgId<A, u64>>,
+    pub reason: DeadLetterReason,
+    pub at: Instant,
+}
+
+impl<A, P> DeadLetter<A, P>
+where
+    A: ActorId + Clone + Debug,
+{
+    pub(crate) fn new(msg: Message<A, P>, reason: DeadLetterReason) -> Self {
+        Self {
+            msg,
+            reason,
+            at: Instant::now(),
+        }
+    }
+
+    pub(crate) fn event(&self) -> DeadLetterEvent<A> {
+        DeadLetterEvent {
+            fid: self.msg.fid().as_ref().clone(),
+            tid: self.msg.tid().as_ref().clone(),
+            mid: self.msg.mid().cloned(),
+            reason: self.reason,
+            at: self.at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event() {
+        let msg = Message::new(1_u8, 2_u8, "payload").with_mid(MsgId::from((1, 7)));
+        let dead_letter = DeadLetter::new(msg, DeadLetterReason::MailboxClosed);
+
+        let event = dead_letter.event();
+        assert_eq!(event.fid, 1);
+        assert_eq!(event.tid, 2);
+        assert_eq!(event.mid, Some(MsgId::from((1, 7))));
+        assert_eq!(event.reason, DeadLetterReason::MailboxClosed);
+        assert_eq!(event.reason.to_string(), "mailbox closed");
+    }
+}
@@ -0,0 +1,34 @@
+use crate::{Context, Message};
+use act_identifiers::ActorId;
+use std::fmt::Debug;
+
+/// The Handler trait represents the user supplied behavior of an actor.
+/// The actor task owns the handler, so the handler is the place where the
+/// state of the actor lives. The hooks are called in the following order:
+/// - `started` once, before the first message is handled;
+/// - `handle` for every message received in the actor's inbound channel;
+/// - `stopping` once, when the inbound channel was closed or the actor
+///   was asked to stop through [`Context::stop`];
+/// - `stopped` once, after the actor flushed its last messages.
+pub trait Handler<A, P>: Send + 'static
+where
+    A: ActorId + Debug,
+{
+    fn started(&mut self, _ctx: &mut Context<A, P>) {}
+
+    fn handle(&mut self, msg: Message<A, P>, ctx: &mut Context<A, P>);
+
+    fn stopping(&mut self, _ctx: &mut Context<A, P>) {}
+
+    fn stopped(&mut self) {}
+}
+
+impl<A, P, F> Handler<A, P> for F
+where
+    A: ActorId + Debug,
+    F: FnMut(Message<A, P>, &mut Context<A, P>) + Send + 'static,
+{
+    fn handle(&mut self, msg: Message<A, P>, ctx: &mut Context<A, P>) {
+        self(msg, ctx)
+    }
+}
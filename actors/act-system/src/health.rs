@@ -0,0 +1,137 @@
+use std::fmt::Debug;
+use tokio::time::Instant;
+
+/// The aggregated health of the bus and of its actors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    /// All the registered actors are running and can take more messages.
+    Healthy,
+    /// Some actors are restarting, stopped or have a full mailbox.
+    Degraded,
+    /// There are registered actors, but none of them is running.
+    Unhealthy,
+}
+
+/// The status of a registered actor, as seen by the bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActorStatus {
+    Running,
+    /// The actor panicked and its supervisor is restarting it.
+    Restarting,
+    /// The mailbox of the actor is closed.
+    Stopped,
+}
+
+#[derive(Debug, Clone)]
+pub struct ActorHealth<A> {
+    pub aid: A,
+    pub status: ActorStatus,
+    /// The number of messages waiting in the mailbox.
+    pub mailbox_depth: usize,
+    /// The maximum number of messages the mailbox can hold, `None` if unbounded.
+    pub mailbox_capacity: Option<usize>,
+    /// When the bus delivered the last message to the actor.
+    pub last_message: Option<Instant>,
+}
+
+impl<A> ActorHealth<A> {
+    pub fn is_mailbox_full(&self) -> bool {
+        self.mailbox_capacity
+            .is_some_and(|capacity| self.mailbox_depth >= capacity)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthResponse<A> {
+    pub health: Health,
+    pub actors: Vec<ActorHealth<A>>,
+}
+
+impl<A> HealthResponse<A> {
+    /// Aggregates the health of the given actors.
+    pub fn new(actors: Vec<ActorHealth<A>>) -> Self {
+        let running = actors
+            .iter()
+            .filter(|actor| actor.status == ActorStatus::Running)
+            .count();
+        let full = actors.iter().any(|actor| actor.is_mailbox_full());
+
+        let health = if !actors.is_empty() && running == 0 {
+            Health::Unhealthy
+        } else if running < actors.len() || full {
+            Health::Degraded
+        } else {
+            Health::Healthy
+        };
+
+        Self { health, actors }
+    }
+
+    /// Returns `true` if the system can take traffic, which is the
+    /// case as long as it is not [`Health::Unhealthy`].
+    pub fn is_ready(&self) -> bool {
+        self.health != Health::Unhealthy
+    }
+
+    pub fn actor(&self, aid: &A) -> Option<&ActorHealth<A>>
+    where
+        A: PartialEq,
+    {
+        self.actors.iter().find(|actor| &actor.aid == aid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn actor(aid: u8, status: ActorStatus, mailbox_depth: usize) -> ActorHealth<u8> {
+        ActorHealth {
+            aid,
+            status,
+            mailbox_depth,
+            mailbox_capacity: Some(10),
+            last_message: None,
+        }
+    }
+
+    #[test]
+    fn healthy() {
+        let health = HealthResponse::new(vec![
+            actor(1, ActorStatus::Running, 0),
+            actor(2, ActorStatus::Running, 9),
+        ]);
+        assert_eq!(health.health, Health::Healthy);
+        assert!(health.is_ready());
+    }
+
+    #[test]
+    fn healthy_without_actors() {
+        let health = HealthResponse::<u8>::new(vec![]);
+        assert_eq!(health.health, Health::Healthy);
+    }
+
+    #[test]
+    fn degraded() {
+        let health = HealthResponse::new(vec![
+            actor(1, ActorStatus::Running, 0),
+            actor(2, ActorStatus::Restarting, 0),
+        ]);
+        assert_eq!(health.health, Health::Degraded);
+        assert!(health.is_ready());
+
+        let health = HealthResponse::new(vec![actor(1, ActorStatus::Running, 10)]);
+        assert_eq!(health.health, Health::Degraded);
+    }
+
+    #[test]
+    fn unhealthy() {
+        let health = HealthResponse::new(vec![
+            actor(1, ActorStatus::Stopped, 0),
+            actor(2, ActorStatus::Restarting, 0),
+        ]);
+        assert_eq!(health.health, Health::Unhealthy);
+        assert!(!health.is_ready());
+        assert_eq!(health.actor(&2).unwrap().status, ActorStatus::Restarting);
+    }
+}
@@ -0,0 +1,468 @@
+use crate::{
+    wire::{decode_id, encode_frame},
+    Wire,
+};
+use act_identifiers::{ActorId, MsgId};
+use anyhow::{anyhow, Result};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    fs::{File, OpenOptions},
+    hash::Hash,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+/// The Journal stores the events of persistent actors, and their snapshots,
+/// so an actor can rebuild its state when it restarts. The events of an
+/// actor are identified by the actor id and a sequence number, starting
+/// at 1, which the journal requires to be contiguous.
+///
+/// The calls are synchronous and may block on I/O. From async code, run
+/// them with [`tokio::task::spawn_blocking`] or
+/// [`tokio::task::block_in_place`]; [`PersistentActor`](crate::PersistentActor)
+/// does the latter on a multi-threaded runtime.
+pub trait Journal<A, E, S>: Send + Sync + 'static
+where
+    A: ActorId,
+{
+    /// Appends the event with the given identifier to the actor's events.
+    fn append(&self, eid: &MsgId<A, u64>, event: &E) -> Result<()>;
+
+    /// The events of the actor with a sequence number after the given one, in order.
+    fn replay(&self, aid: &A, after: u64) -> Result<Vec<(u64, E)>>;
+
+    /// Stores the snapshot of the actor state after the event with the given
+    /// sequence number. It replaces the snapshot stored before.
+    fn save_snapshot(&self, aid: &A, seq: u64, snapshot: &S) -> Result<()>;
+
+    /// The latest snapshot of the actor with the sequence number it was taken at.
+    fn load_snapshot(&self, aid: &A) -> Result<Option<(u64, S)>>;
+}
+
+/// The events and the snapshot of one actor.
+struct Entries<E, S> {
+    events: Vec<(u64, E)>,
+    snapshot: Option<(u64, S)>,
+}
+
+impl<E, S> Default for Entries<E, S> {
+    fn default() -> Self {
+        Self {
+            events: Vec::new(),
+            snapshot: None,
+        }
+    }
+}
+
+impl<E, S> Entries<E, S> {
+    fn last_seq(&self) -> u64 {
+        self.events.last().map(|(seq, _)| *seq).unwrap_or(0)
+    }
+}
+
+/// A journal which keeps everything in memory, for tests and for actors
+/// which need to survive restarts but not the process.
+pub struct InMemoryJournal<A, E, S> {
+    entries: Mutex<HashMap<A, Entries<E, S>>>,
+}
+
+impl<A, E, S> Default for InMemoryJournal<A, E, S> {
+    fn default() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<A, E, S> InMemoryJournal<A, E, S>
+where
+    A: Eq + Hash,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The sequence number the next event of the actor must have.
+    fn next_seq(&self, aid: &A) -> u64 {
+        let entries = self.entries.lock().unwrap();
+        entries.get(aid).map(Entries::last_seq).unwrap_or(0) + 1
+    }
+}
+
+/// Checks the event identifier carries the next sequence number.
+fn check_seq<A>(eid: &MsgId<A, u64>, expected: u64) -> Result<()>
+where
+    A: ActorId + Debug,
+{
+    if *eid.mid() == expected {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Failed to append the event {eid:?}, the next sequence number is {expected}"
+        ))
+    }
+}
+
+impl<A, E, S> Journal<A, E, S> for InMemoryJournal<A, E, S>
+where
+    A: ActorId + Clone + Debug + Hash + Send + 'static,
+    E: Clone + Send + 'static,
+    S: Clone + Send + 'static,
+{
+    fn append(&self, eid: &MsgId<A, u64>, event: &E) -> Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        let actor = entries.entry(eid.aid().clone()).or_default();
+
+        check_seq(eid, actor.last_seq() + 1)?;
+        actor.events.push((*eid.mid(), event.clone()));
+        Ok(())
+    }
+
+    fn replay(&self, aid: &A, after: u64) -> Result<Vec<(u64, E)>> {
+        let entries = self.entries.lock().unwrap();
+        let events = entries
+            .get(aid)
+            .map(|actor| {
+                actor
+                    .events
+                    .iter()
+                    .filter(|(seq, _)| *seq > after)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(events)
+    }
+
+    fn save_snapshot(&self, aid: &A, seq: u64, snapshot: &S) -> Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        let actor = entries.entry(aid.clone()).or_default();
+        actor.snapshot = Some((seq, snapshot.clone()));
+        Ok(())
+    }
+
+    fn load_snapshot(&self, aid: &A) -> Result<Option<(u64, S)>> {
+        let entries = self.entries.lock().unwrap();
+        Ok(entries.get(aid).and_then(|actor| actor.snapshot.clone()))
+    }
+}
+
+/// The kinds of records in a journal file.
+const EVENT_RECORD: u8 = 0;
+const SNAPSHOT_RECORD: u8 = 1;
+
+/// A record of a journal file.
+enum Record<A, E, S>
+where
+    A: ActorId,
+{
+    Event(MsgId<A, u64>, E),
+    Snapshot(A, u64, S),
+}
+
+impl<A, E, S> Wire for Record<A, E, S>
+where
+    A: ActorId + act_identifiers::Binary,
+    E: Wire,
+    S: Wire,
+{
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Record::Event(eid, event) => {
+                EVENT_RECORD.encode(buf);
+                eid.encode(buf);
+                event.encode(buf);
+            }
+            Record::Snapshot(aid, seq, snapshot) => {
+                SNAPSHOT_RECORD.encode(buf);
+                aid.encode(buf);
+                seq.encode(buf);
+                snapshot.encode(buf);
+            }
+        }
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        match u8::decode(buf)? {
+            EVENT_RECORD => Ok(Record::Event(decode_id(buf)?, E::decode(buf)?)),
+            SNAPSHOT_RECORD => Ok(Record::Snapshot(
+                decode_id(buf)?,
+                u64::decode(buf)?,
+                S::decode(buf)?,
+            )),
+            kind => Err(anyhow!("Failed to read the journal record kind [{kind}]")),
+        }
+    }
+}
+
+/// The journal file and the length of the records it holds in full.
+struct JournalFile {
+    file: File,
+    len: u64,
+    /// Set when a failed write could not be undone, the file is not
+    /// written anymore.
+    failed: bool,
+}
+
+/// A journal which appends the events and the snapshots to a local file,
+/// as frames of the wire encoding. The file is read back when the journal
+/// is opened, a record cut short by a crash while writing is dropped.
+///
+/// Every write waits for the record to reach the disk, blocking the calling
+/// thread: see [`Journal`] about calling it from async code.
+pub struct FileJournal<A, E, S> {
+    path: PathBuf,
+    /// Held across the check, the write and the update of the memory.
+    file: Mutex<JournalFile>,
+    memory: InMemoryJournal<A, E, S>,
+}
+
+impl<A, E, S> FileJournal<A, E, S>
+where
+    A: ActorId + Clone + Debug + Hash + act_identifiers::Binary + Send + 'static,
+    E: Clone + Wire + Send + 'static,
+    S: Clone + Wire + Send + 'static,
+{
+    /// Opens the journal file, creating it if it does not exist.
+    pub fn open<T>(path: T) -> Result<Self>
+    where
+        T: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)
+            .map_err(|e| anyhow!("Failed to open the journal {path:?} [{e}]"))?;
+
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)
+            .map_err(|e| anyhow!("Failed to read the journal {path:?} [{e}]"))?;
+
+        let memory = InMemoryJournal::new();
+        let mut buf = bytes.as_slice();
+        while let Some(mut record) = next_frame(&mut buf) {
+            match Record::<A, E, S>::decode(&mut record)? {
+                Record::Event(eid, event) => memory.append(&eid, &event)?,
+                Record::Snapshot(aid, seq, snapshot) => {
+                    memory.save_snapshot(&aid, seq, &snapshot)?
+                }
+            }
+        }
+
+        // Drop a torn record, so the next ones are appended after the last good one.
+        let valid_len = (bytes.len() - buf.len()) as u64;
+        if !buf.is_empty() {
+            file.set_len(valid_len)
+                .map_err(|e| anyhow!("Failed to truncate the journal {path:?} [{e}]"))?;
+        }
+
+        Ok(Self {
+            path,
+            file: Mutex::new(JournalFile {
+                file,
+                len: valid_len,
+                failed: false,
+            }),
+            memory,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn file(&self) -> MutexGuard<'_, JournalFile> {
+        self.file.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Appends the record to the file as a frame. The record is on disk
+    /// when the call returns. A failed write is cut off the file, so the
+    /// next record follows the last good one.
+    fn write(&self, file: &mut JournalFile, record: &Record<A, E, S>) -> Result<()> {
+        if file.failed {
+            return Err(anyhow!(
+                "Failed to write the journal {:?}, it is damaged by a previous write",
+                self.path
+            ));
+        }
+        let frame = encode_frame(record)?;
+
+        if let Err(e) = file
+            .file
+            .write_all(&frame)
+            .and_then(|_| file.file.sync_data())
+        {
+            if let Err(e) = file.file.set_len(file.len) {
+                tracing::warn!(path = ?self.path, "Failed to truncate the journal [{e}]");
+                file.failed = true;
+            }
+            return Err(anyhow!("Failed to write the journal {:?} [{e}]", self.path));
+        }
+        file.len += frame.len() as u64;
+        Ok(())
+    }
+}
+
+/// Takes the next complete frame from the front of the buffer.
+fn next_frame<'a>(buf: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = u32::from_be_bytes(buf.get(..4)?.try_into().ok()?) as usize;
+    let frame = buf.get(4..4 + len)?;
+    *buf = &buf[4 + len..];
+    Some(frame)
+}
+
+impl<A, E, S> Journal<A, E, S> for FileJournal<A, E, S>
+where
+    A: ActorId + Clone + Debug + Hash + act_identifiers::Binary + Send + 'static,
+    E: Clone + Wire + Send + 'static,
+    S: Clone + Wire + Send + 'static,
+{
+    fn append(&self, eid: &MsgId<A, u64>, event: &E) -> Result<()> {
+        let mut file = self.file();
+        // Check the sequence before writing, so the file never holds a gap.
+        check_seq(eid, self.memory.next_seq(eid.aid()))?;
+        self.write(&mut file, &Record::Event(eid.clone(), event.clone()))?;
+        self.memory.append(eid, event)
+    }
+
+    fn replay(&self, aid: &A, after: u64) -> Result<Vec<(u64, E)>> {
+        self.memory.replay(aid, after)
+    }
+
+    fn save_snapshot(&self, aid: &A, seq: u64, snapshot: &S) -> Result<()> {
+        let mut file = self.file();
+        let record = Record::Snapshot(aid.clone(), seq, snapshot.clone());
+        self.write(&mut file, &record)?;
+        self.memory.save_snapshot(aid, seq, snapshot)
+    }
+
+    fn load_snapshot(&self, aid: &A) -> Result<Option<(u64, S)>> {
+        self.memory.load_snapshot(aid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A journal file path which no other test uses.
+    fn journal_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("act-system-{}-{name}.journal", std::process::id()))
+    }
+
+    #[test]
+    fn in_memory() {
+        let journal = InMemoryJournal::<u8, String, u32>::new();
+
+        journal
+            .append(&MsgId::from((1, 1)), &"a".to_string())
+            .unwrap();
+        journal
+            .append(&MsgId::from((1, 2)), &"b".to_string())
+            .unwrap();
+        journal
+            .append(&MsgId::from((2, 1)), &"c".to_string())
+            .unwrap();
+
+        let events = journal.replay(&1, 0).unwrap();
+        assert_eq!(events, vec![(1, "a".to_string()), (2, "b".to_string())]);
+        assert_eq!(journal.replay(&1, 1).unwrap().len(), 1);
+        assert!(journal.replay(&3, 0).unwrap().is_empty());
+
+        assert!(journal.load_snapshot(&1).unwrap().is_none());
+        journal.save_snapshot(&1, 2, &7).unwrap();
+        assert_eq!(journal.load_snapshot(&1).unwrap(), Some((2, 7)));
+    }
+
+    #[test]
+    fn out_of_sequence() {
+        let journal = InMemoryJournal::<u8, u32, ()>::new();
+
+        assert!(journal.append(&MsgId::from((1, 2)), &0).is_err());
+        journal.append(&MsgId::from((1, 1)), &0).unwrap();
+        assert!(journal.append(&MsgId::from((1, 1)), &0).is_err());
+    }
+
+    #[test]
+    fn file() {
+        let path = journal_path("file");
+        let _ = std::fs::remove_file(&path);
+
+        let journal = FileJournal::<u8, String, u32>::open(&path).unwrap();
+        journal
+            .append(&MsgId::from((1, 1)), &"a".to_string())
+            .unwrap();
+        journal.save_snapshot(&1, 1, &5).unwrap();
+        journal
+            .append(&MsgId::from((1, 2)), &"b".to_string())
+            .unwrap();
+        assert!(journal
+            .append(&MsgId::from((1, 4)), &"d".to_string())
+            .is_err());
+        drop(journal);
+
+        let journal = FileJournal::<u8, String, u32>::open(&path).unwrap();
+        assert_eq!(journal.load_snapshot(&1).unwrap(), Some((1, 5)));
+        assert_eq!(journal.replay(&1, 1).unwrap(), vec![(2, "b".to_string())]);
+        journal
+            .append(&MsgId::from((1, 3)), &"c".to_string())
+            .unwrap();
+        drop(journal);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn torn_record() {
+        let path = journal_path("torn");
+        let _ = std::fs::remove_file(&path);
+
+        let journal = FileJournal::<u8, u32, ()>::open(&path).unwrap();
+        journal.append(&MsgId::from((1, 1)), &10).unwrap();
+        journal.append(&MsgId::from((1, 2)), &20).unwrap();
+        drop(journal);
+
+        // a crash cut the last record short.
+        let len = std::fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 1).unwrap();
+        drop(file);
+
+        let journal = FileJournal::<u8, u32, ()>::open(&path).unwrap();
+        assert_eq!(journal.replay(&1, 0).unwrap(), vec![(1, 10)]);
+        journal.append(&MsgId::from((1, 2)), &21).unwrap();
+        drop(journal);
+
+        let journal = FileJournal::<u8, u32, ()>::open(&path).unwrap();
+        assert_eq!(journal.replay(&1, 0).unwrap(), vec![(1, 10), (2, 21)]);
+        drop(journal);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn record_too_large() {
+        let path = journal_path("large");
+        let _ = std::fs::remove_file(&path);
+
+        // the record does not fit in a frame and is not written.
+        let journal = FileJournal::<u8, String, ()>::open(&path).unwrap();
+        let event = "x".repeat(crate::MAX_FRAME_LEN);
+        assert!(journal.append(&MsgId::from((1, 1)), &event).is_err());
+        assert!(journal.replay(&1, 0).unwrap().is_empty());
+        journal
+            .append(&MsgId::from((1, 1)), &"a".to_string())
+            .unwrap();
+        drop(journal);
+
+        let journal = FileJournal::<u8, String, ()>::open(&path).unwrap();
+        assert_eq!(journal.replay(&1, 0).unwrap(), vec![(1, "a".to_string())]);
+        drop(journal);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,9 +1,45 @@
 mod actor;
 mod bus;
+mod context;
 mod ctrl;
+mod dead_letter;
+mod handler;
+mod health;
+mod journal;
+mod mailbox;
+mod metrics;
 mod msg;
+mod persistence;
+mod registry;
+mod remote;
+mod router;
+mod shutdown;
+mod supervisor;
+mod system;
+#[cfg(any(test, feature = "testkit"))]
+mod testkit;
+mod timer;
+mod wire;
 
 pub use actor::*;
 pub use bus::*;
+pub use context::*;
 pub use ctrl::*;
+pub use dead_letter::*;
+pub use handler::*;
+pub use health::*;
+pub use journal::*;
+pub use mailbox::*;
+pub use metrics::*;
 pub use msg::*;
+pub use persistence::*;
+pub use registry::{ActorPath, DeathWatch, ExitReason, Terminated};
+pub use remote::*;
+pub use router::{Resizer, Router, RouterSpec, Routing, WorkerId};
+pub use shutdown::ShutdownReport;
+pub use supervisor::*;
+pub use system::*;
+#[cfg(any(test, feature = "testkit"))]
+pub use testkit::*;
+pub use timer::TimerHandle;
+pub use wire::{Wire, MAX_FRAME_LEN};
@@ -0,0 +1,547 @@
+use crate::{Bus, DeadLetterReason, Message, Priority};
+use act_identifiers::ActorId;
+use std::{
+    collections::VecDeque,
+    fmt::{Debug, Display},
+    sync::{Arc, Mutex},
+};
+use tokio::sync::{mpsc::UnboundedSender, Notify};
+
+/// The default number of messages a mailbox can hold.
+pub const DEFAULT_MAILBOX_CAPACITY: usize = 10;
+
+/// What a bounded mailbox does with a message sent while it is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The sender waits until there is room for the message. The bus does
+    /// not wait: it keeps the message aside until there is room, so the
+    /// other actors are not delayed.
+    Block,
+    /// The new message is discarded.
+    DropNewest,
+    /// The oldest message in the mailbox is discarded to make room.
+    DropOldest,
+    /// The send fails right away, giving the message back to the sender.
+    FailFast,
+}
+
+/// The configuration of an actor's mailbox. When `priority` is enabled,
+/// the messages with [`Priority::High`] are handled before all the other
+/// messages and they are never refused because the mailbox is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MailboxConfig {
+    capacity: Option<usize>,
+    overflow: OverflowPolicy,
+    priority: bool,
+}
+
+impl Default for MailboxConfig {
+    fn default() -> Self {
+        Self::bounded(DEFAULT_MAILBOX_CAPACITY)
+    }
+}
+
+impl MailboxConfig {
+    pub fn bounded(capacity: usize) -> Self {
+        assert!(capacity > 0, "the mailbox capacity must be greater than 0");
+        Self {
+            capacity: Some(capacity),
+            overflow: OverflowPolicy::Block,
+            priority: false,
+        }
+    }
+
+    pub fn unbounded() -> Self {
+        Self {
+            capacity: None,
+            overflow: OverflowPolicy::Block,
+            priority: false,
+        }
+    }
+
+    pub fn with_overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn with_priority(mut self) -> Self {
+        self.priority = true;
+        self
+    }
+
+    /// The maximum number of messages, `None` if the mailbox is unbounded.
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    pub fn overflow(&self) -> OverflowPolicy {
+        self.overflow
+    }
+
+    pub fn is_priority(&self) -> bool {
+        self.priority
+    }
+}
+
+/// The error returned when a message cannot be put in a mailbox.
+/// It gives the message back to the sender.
+pub enum MailboxError<M> {
+    /// The receiver closed the mailbox or it was dropped.
+    Closed(M),
+    /// The mailbox is full and its policy is [`OverflowPolicy::FailFast`],
+    /// or it is [`OverflowPolicy::Block`] and the send could not wait.
+    Full(M),
+}
+
+impl<M> MailboxError<M> {
+    pub fn into_inner(self) -> M {
+        match self {
+            MailboxError::Closed(msg) | MailboxError::Full(msg) => msg,
+        }
+    }
+}
+
+impl<M> Debug for MailboxError<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MailboxError::Closed(_) => write!(f, "Closed(..)"),
+            MailboxError::Full(_) => write!(f, "Full(..)"),
+        }
+    }
+}
+
+impl<M> Display for MailboxError<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MailboxError::Closed(_) => write!(f, "the mailbox is closed"),
+            MailboxError::Full(_) => write!(f, "the mailbox is full"),
+        }
+    }
+}
+
+impl<M> std::error::Error for MailboxError<M> {}
+
+/// The outcome of a send: the message discarded to honour the overflow
+/// policy, if any, or the error giving the message back.
+pub type SendResult<A, P> = Result<Option<Message<A, P>>, MailboxError<Message<A, P>>>;
+
+struct State<M> {
+    high: VecDeque<M>,
+    normal: VecDeque<M>,
+    closed: bool,
+    senders: usize,
+}
+
+struct Shared<M> {
+    config: MailboxConfig,
+    state: Mutex<State<M>>,
+    /// Notified when a message is added or the last sender is dropped.
+    items: Notify,
+    /// Notified when a message is removed or the mailbox is closed.
+    space: Notify,
+}
+
+/// Creates a mailbox, returning its sending and receiving halves.
+pub fn mailbox<A, P>(config: MailboxConfig) -> (MailboxSender<A, P>, MailboxReceiver<A, P>)
+where
+    A: ActorId + Debug,
+{
+    let shared = Arc::new(Shared {
+        config,
+        state: Mutex::new(State {
+            high: VecDeque::new(),
+            normal: VecDeque::new(),
+            closed: false,
+            senders: 1,
+        }),
+        items: Notify::new(),
+        space: Notify::new(),
+    });
+
+    let tx = MailboxSender {
+        shared: shared.clone(),
+    };
+    let rx = MailboxReceiver {
+        shared,
+        dead_letters: None,
+    };
+    (tx, rx)
+}
+
+/// The sending half of a mailbox, which can be cloned.
+pub struct MailboxSender<A, P>
+where
+    A: ActorId + Debug,
+{
+    shared: Arc<Shared<Message<A, P>>>,
+}
+
+impl<A, P> Clone for MailboxSender<A, P>
+where
+    A: ActorId + Debug,
+{
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<A, P> Debug for MailboxSender<A, P>
+where
+    A: ActorId + Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MailboxSender")
+            .field("config", &self.shared.config)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<A, P> Drop for MailboxSender<A, P>
+where
+    A: ActorId + Debug,
+{
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.senders -= 1;
+        if state.senders == 0 {
+            drop(state);
+            self.shared.items.notify_one();
+        }
+    }
+}
+
+impl<A, P> MailboxSender<A, P>
+where
+    A: ActorId + Debug,
+{
+    /// Puts the message in the mailbox, applying the overflow policy if the
+    /// mailbox is full. Returns the message discarded to honour the policy,
+    /// if any, which is the sent message itself for [`OverflowPolicy::DropNewest`].
+    pub async fn send(&self, mut msg: Message<A, P>) -> SendResult<A, P> {
+        loop {
+            // Register for the wake up before checking, so no removal is missed.
+            let space = self.shared.space.notified();
+            tokio::pin!(space);
+            space.as_mut().enable();
+
+            match self.try_send(msg) {
+                Err(MailboxError::Full(full))
+                    if self.shared.config.overflow == OverflowPolicy::Block =>
+                {
+                    msg = full;
+                }
+                res => return res,
+            }
+
+            space.await;
+        }
+    }
+
+    /// Puts the message in the mailbox without waiting. It behaves like
+    /// [`send`](Self::send), except that a full mailbox with the
+    /// [`OverflowPolicy::Block`] policy gives the message back as
+    /// [`MailboxError::Full`] instead of waiting for room.
+    pub fn try_send(&self, msg: Message<A, P>) -> SendResult<A, P> {
+        let config = self.shared.config;
+
+        let mut state = self.shared.state.lock().unwrap();
+        if state.closed {
+            return Err(MailboxError::Closed(msg));
+        }
+
+        if config.priority && msg.priority() == Priority::High {
+            state.high.push_back(msg);
+            drop(state);
+            self.shared.items.notify_one();
+            return Ok(None);
+        }
+
+        let full = config
+            .capacity
+            .is_some_and(|capacity| state.normal.len() >= capacity);
+        if !full {
+            state.normal.push_back(msg);
+            drop(state);
+            self.shared.items.notify_one();
+            return Ok(None);
+        }
+
+        match config.overflow {
+            OverflowPolicy::Block | OverflowPolicy::FailFast => Err(MailboxError::Full(msg)),
+            OverflowPolicy::DropNewest => Ok(Some(msg)),
+            OverflowPolicy::DropOldest => {
+                let oldest = state.normal.pop_front();
+                state.normal.push_back(msg);
+                drop(state);
+                self.shared.items.notify_one();
+                Ok(oldest)
+            }
+        }
+    }
+
+    /// The number of messages waiting in the mailbox.
+    pub fn len(&self) -> usize {
+        let state = self.shared.state.lock().unwrap();
+        state.high.len() + state.normal.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.shared.state.lock().unwrap().closed
+    }
+
+    pub fn config(&self) -> &MailboxConfig {
+        &self.shared.config
+    }
+}
+
+/// The receiving half of a mailbox.
+pub struct MailboxReceiver<A, P>
+where
+    A: ActorId + Debug,
+{
+    shared: Arc<Shared<Message<A, P>>>,
+    /// Where the messages left in the mailbox go when it is dropped.
+    dead_letters: Option<UnboundedSender<(Message<A, P>, DeadLetterReason)>>,
+}
+
+impl<A, P> MailboxReceiver<A, P>
+where
+    A: ActorId + Debug,
+{
+    /// Hands the messages still in the mailbox when it is dropped to the
+    /// dead-letter office of the bus, instead of discarding them.
+    pub(crate) fn with_dead_letters(mut self, bus: &Bus<A, P>) -> Self {
+        self.dead_letters = Some(bus.undelivered_tx());
+        self
+    }
+
+    /// Receives the next message, the high priority ones first. Returns
+    /// `None` once the mailbox is empty and either it was closed or all
+    /// the senders were dropped.
+    pub async fn recv(&mut self) -> Option<Message<A, P>> {
+        loop {
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                let msg = match state.high.pop_front() {
+                    Some(msg) => Some(msg),
+                    None => state.normal.pop_front(),
+                };
+                if let Some(msg) = msg {
+                    drop(state);
+                    self.shared.space.notify_waiters();
+                    return Some(msg);
+                }
+                if state.closed || state.senders == 0 {
+                    return None;
+                }
+            }
+
+            self.shared.items.notified().await;
+        }
+    }
+
+    /// Refuses any new message. The messages already in the mailbox
+    /// can still be received.
+    pub fn close(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.space.notify_waiters();
+    }
+}
+
+impl<A, P> Drop for MailboxReceiver<A, P>
+where
+    A: ActorId + Debug,
+{
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.closed = true;
+        let high = std::mem::take(&mut state.high);
+        let normal = std::mem::take(&mut state.normal);
+        drop(state);
+        self.shared.space.notify_waiters();
+
+        if let Some(dead_letters) = &self.dead_letters {
+            for msg in high.into_iter().chain(normal) {
+                let _ = dead_letters.send((msg, DeadLetterReason::ActorStopped));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn msg(payload: u32) -> Message<u8, u32> {
+        Message::new(1, 2, payload)
+    }
+
+    async fn recv_all(rx: &mut MailboxReceiver<u8, u32>, n: usize) -> Vec<u32> {
+        let mut payloads = Vec::new();
+        for _ in 0..n {
+            payloads.push(rx.recv().await.unwrap().into_payload());
+        }
+        payloads
+    }
+
+    #[tokio::test]
+    async fn fifo() {
+        let (tx, mut rx) = mailbox::<u8, u32>(MailboxConfig::default());
+        for i in 0..3 {
+            assert!(tx.send(msg(i)).await.unwrap().is_none());
+        }
+        assert_eq!(tx.len(), 3);
+        assert_eq!(recv_all(&mut rx, 3).await, vec![0, 1, 2]);
+        assert!(tx.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unbounded() {
+        let (tx, mut rx) = mailbox::<u8, u32>(MailboxConfig::unbounded());
+        for i in 0..100 {
+            tx.send(msg(i)).await.unwrap();
+        }
+        assert_eq!(tx.len(), 100);
+        assert_eq!(recv_all(&mut rx, 100).await, (0..100).collect::<Vec<_>>());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn block() {
+        let (tx, mut rx) = mailbox::<u8, u32>(MailboxConfig::bounded(1));
+        tx.send(msg(0)).await.unwrap();
+
+        let send = tokio::time::timeout(Duration::from_millis(10), tx.send(msg(1))).await;
+        assert!(send.is_err());
+
+        let sender = tx.clone();
+        let blocked = tokio::spawn(async move { sender.send(msg(2)).await.is_ok() });
+        assert_eq!(recv_all(&mut rx, 1).await, vec![0]);
+        assert!(blocked.await.unwrap());
+        assert_eq!(recv_all(&mut rx, 1).await, vec![2]);
+    }
+
+    #[tokio::test]
+    async fn try_send() {
+        let (tx, mut rx) = mailbox::<u8, u32>(MailboxConfig::bounded(1));
+        assert!(tx.try_send(msg(0)).unwrap().is_none());
+
+        match tx.try_send(msg(1)) {
+            Err(MailboxError::Full(msg)) => assert_eq!(msg.into_payload(), 1),
+            other => panic!("unexpected {other:?}"),
+        }
+        assert_eq!(recv_all(&mut rx, 1).await, vec![0]);
+        assert!(tx.try_send(msg(2)).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn drop_newest() {
+        let config = MailboxConfig::bounded(2).with_overflow(OverflowPolicy::DropNewest);
+        let (tx, mut rx) = mailbox::<u8, u32>(config);
+        tx.send(msg(0)).await.unwrap();
+        tx.send(msg(1)).await.unwrap();
+
+        let dropped = tx.send(msg(2)).await.unwrap().unwrap();
+        assert_eq!(dropped.into_payload(), 2);
+        assert_eq!(recv_all(&mut rx, 2).await, vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn drop_oldest() {
+        let config = MailboxConfig::bounded(2).with_overflow(OverflowPolicy::DropOldest);
+        let (tx, mut rx) = mailbox::<u8, u32>(config);
+        tx.send(msg(0)).await.unwrap();
+        tx.send(msg(1)).await.unwrap();
+
+        let dropped = tx.send(msg(2)).await.unwrap().unwrap();
+        assert_eq!(dropped.into_payload(), 0);
+        assert_eq!(recv_all(&mut rx, 2).await, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn fail_fast() {
+        let config = MailboxConfig::bounded(1).with_overflow(OverflowPolicy::FailFast);
+        let (tx, mut rx) = mailbox::<u8, u32>(config);
+        tx.send(msg(0)).await.unwrap();
+
+        match tx.send(msg(1)).await {
+            Err(MailboxError::Full(msg)) => assert_eq!(msg.into_payload(), 1),
+            other => panic!("unexpected {other:?}"),
+        }
+        assert_eq!(recv_all(&mut rx, 1).await, vec![0]);
+    }
+
+    #[tokio::test]
+    async fn priority() {
+        let config = MailboxConfig::bounded(2)
+            .with_overflow(OverflowPolicy::FailFast)
+            .with_priority();
+        let (tx, mut rx) = mailbox::<u8, u32>(config);
+        tx.send(msg(0)).await.unwrap();
+        tx.send(msg(1)).await.unwrap();
+
+        // high priority messages jump the queue, even when it is full.
+        tx.send(msg(10).with_priority(Priority::High))
+            .await
+            .unwrap();
+        tx.send(msg(11).with_priority(Priority::High))
+            .await
+            .unwrap();
+
+        assert_eq!(recv_all(&mut rx, 4).await, vec![10, 11, 0, 1]);
+    }
+
+    #[tokio::test]
+    async fn without_priority() {
+        let (tx, mut rx) = mailbox::<u8, u32>(MailboxConfig::default());
+        tx.send(msg(0)).await.unwrap();
+        tx.send(msg(1).with_priority(Priority::High)).await.unwrap();
+
+        assert_eq!(recv_all(&mut rx, 2).await, vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn close() {
+        let (tx, mut rx) = mailbox::<u8, u32>(MailboxConfig::default());
+        tx.send(msg(0)).await.unwrap();
+        rx.close();
+
+        assert!(tx.is_closed());
+        assert!(matches!(
+            tx.send(msg(1)).await,
+            Err(MailboxError::Closed(_))
+        ));
+        assert_eq!(recv_all(&mut rx, 1).await, vec![0]);
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn senders_dropped() {
+        let (tx, mut rx) = mailbox::<u8, u32>(MailboxConfig::default());
+        let tx2 = tx.clone();
+        tx.send(msg(0)).await.unwrap();
+        drop(tx);
+        drop(tx2);
+
+        assert_eq!(recv_all(&mut rx, 1).await, vec![0]);
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn receiver_dropped() {
+        let (tx, rx) = mailbox::<u8, u32>(MailboxConfig::default());
+        drop(rx);
+        assert!(matches!(
+            tx.send(msg(0)).await,
+            Err(MailboxError::Closed(_))
+        ));
+    }
+}
@@ -0,0 +1,26 @@
+use std::time::Duration;
+
+/// The Metrics trait receives the measurements of the message flow, so
+/// they can be exported to the monitoring system in use. Every method
+/// does nothing by default, an implementation picks the ones it needs.
+/// The methods are called on the hot path, they should not block.
+pub trait Metrics<A>: Send + Sync + 'static {
+    /// The bus delivered a message to the mailbox of the actor.
+    fn message_routed(&self, _tid: &A) {}
+
+    /// The bus could not deliver a message to the actor.
+    fn message_dead(&self, _tid: &A) {}
+
+    /// The number of messages waiting in the mailbox of the actor,
+    /// sampled each time the bus delivers a message to it.
+    fn mailbox_depth(&self, _aid: &A, _depth: usize) {}
+
+    /// How long the handler of the actor took to handle a message.
+    fn handler_latency(&self, _aid: &A, _latency: Duration) {}
+}
+
+/// The metrics used when none are given, which discard everything.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopMetrics;
+
+impl<A> Metrics<A> for NoopMetrics {}
@@ -1,21 +1,34 @@
 use std::fmt::Debug;
 
-use act_identifiers::{ActorId, FromId, ToId};
+use act_identifiers::{ActorId, FromId, MsgId, ToId};
 
-#[derive(Debug)]
-pub struct Message<A>
+/// The priority of a message. High priority messages jump the queue
+/// of the mailboxes which have priority enabled.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    #[default]
+    Normal,
+    High,
+}
+
+#[derive(Debug, Clone)]
+pub struct Message<A, P>
 where
     A: ActorId + Debug,
 {
     fid: FromId<A>,
     tid: ToId<A>,
+    mid: Option<MsgId<A, u64>>,
+    cid: Option<MsgId<A, u64>>,
+    priority: Priority,
+    payload: P,
 }
 
-impl<A> Message<A>
+impl<A, P> Message<A, P>
 where
     A: ActorId + Debug,
 {
-    pub fn new<F, T>(f: F, t: T) -> Self
+    pub fn new<F, T>(f: F, t: T, payload: P) -> Self
     where
         F: Into<FromId<A>>,
         T: Into<ToId<A>>,
@@ -23,6 +36,10 @@ where
         Self {
             fid: f.into(),
             tid: t.into(),
+            mid: None,
+            cid: None,
+            priority: Priority::Normal,
+            payload,
         }
     }
 
@@ -33,4 +50,84 @@ where
     pub fn tid(&self) -> &ToId<A> {
         &self.tid
     }
+
+    /// The identifier of the message, if the sender assigned one.
+    pub fn mid(&self) -> Option<&MsgId<A, u64>> {
+        self.mid.as_ref()
+    }
+
+    /// The correlation identifier, which is the identifier of the
+    /// message this message is a reply to.
+    pub fn cid(&self) -> Option<&MsgId<A, u64>> {
+        self.cid.as_ref()
+    }
+
+    pub fn with_mid(mut self, mid: MsgId<A, u64>) -> Self {
+        self.mid = Some(mid);
+        self
+    }
+
+    pub fn with_cid(mut self, cid: MsgId<A, u64>) -> Self {
+        self.cid = Some(cid);
+        self
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn payload(&self) -> &P {
+        &self.payload
+    }
+
+    /// Consumes the message returning the data it carries.
+    pub fn into_payload(self) -> P {
+        self.payload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new() {
+        let msg = Message::new(1_u8, 2_u8, "payload");
+        assert_eq!(msg.fid().as_ref(), &1);
+        assert_eq!(msg.tid().as_ref(), &2);
+        assert_eq!(msg.payload(), &"payload");
+    }
+
+    #[test]
+    fn with_ids() {
+        let msg = Message::new(1_u8, 2_u8, "payload");
+        assert!(msg.mid().is_none());
+        assert!(msg.cid().is_none());
+
+        let msg = msg
+            .with_mid(MsgId::from((1, 10)))
+            .with_cid(MsgId::from((2, 20)));
+        assert_eq!(msg.mid(), Some(&MsgId::from((1, 10))));
+        assert_eq!(msg.cid(), Some(&MsgId::from((2, 20))));
+    }
+
+    #[test]
+    fn with_priority() {
+        let msg = Message::new(1_u8, 2_u8, "payload");
+        assert_eq!(msg.priority(), Priority::Normal);
+
+        let msg = msg.with_priority(Priority::High);
+        assert_eq!(msg.priority(), Priority::High);
+    }
+
+    #[test]
+    fn into_payload() {
+        let msg = Message::new(1_u8, 2_u8, vec![1, 2, 3]);
+        assert_eq!(msg.into_payload(), vec![1, 2, 3]);
+    }
 }
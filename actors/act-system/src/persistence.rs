@@ -0,0 +1,309 @@
+use crate::{Context, Handler, Journal, Message};
+use act_identifiers::{ActorId, MsgId};
+use std::{fmt::Debug, marker::PhantomData, sync::Arc};
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+/// The Persistent trait describes an actor whose state changes only
+/// through events. The commands it receives are turned into events, which
+/// are journaled before they are applied, so the state can be rebuilt by
+/// applying them again when the actor restarts.
+pub trait Persistent<A, P>: Send + 'static
+where
+    A: ActorId + Debug,
+{
+    type Event: Send + 'static;
+    type Snapshot: Send + 'static;
+
+    /// Handles a command, returning the events it produces and the payload
+    /// of the reply to the command, if any. The reply is sent only once the
+    /// events are journaled, so it never confirms an event which could be
+    /// lost. The state must not change here, it changes when the events
+    /// are applied.
+    fn handle_command(
+        &mut self,
+        msg: &Message<A, P>,
+        ctx: &mut Context<A, P>,
+    ) -> (Vec<Self::Event>, Option<P>);
+
+    /// Applies an event to the state, either a new one or a replayed one.
+    fn apply(&mut self, event: &Self::Event);
+
+    /// Takes a snapshot of the current state.
+    fn snapshot(&self) -> Self::Snapshot;
+
+    /// Restores the state from a snapshot, before the following events are replayed.
+    fn restore(&mut self, snapshot: Self::Snapshot);
+}
+
+/// A PersistentActor runs a [`Persistent`] handler against a journal. It
+/// recovers the state when it starts, from the latest snapshot and the
+/// events journaled after it, then journals the events of each command.
+/// The events are identified by a [`MsgId`] made of the actor id and
+/// their sequence number.
+///
+/// A journal failure panics the actor, since its state could no longer be
+/// recovered: under a supervisor the actor restarts and recovers again.
+///
+/// The journal calls block the actor task. On a multi-threaded runtime they
+/// run in [`tokio::task::block_in_place`], so the other tasks move to
+/// another worker meanwhile; on a current-thread runtime they block it.
+pub struct PersistentActor<A, P, H, J>
+where
+    A: ActorId + Debug,
+    H: Persistent<A, P>,
+{
+    handler: H,
+    journal: Arc<J>,
+    seq: u64,
+    snapshot_every: Option<u64>,
+    _marker: PhantomData<fn(A, P)>,
+}
+
+impl<A, P, H, J> PersistentActor<A, P, H, J>
+where
+    A: ActorId + Debug,
+    H: Persistent<A, P>,
+    J: Journal<A, H::Event, H::Snapshot>,
+{
+    /// Creates the actor with the state of the handler before any event.
+    /// Share the journal with the factory restarting the actor, so the
+    /// restarted actor recovers the state.
+    pub fn new(handler: H, journal: Arc<J>) -> Self {
+        Self {
+            handler,
+            journal,
+            seq: 0,
+            snapshot_every: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Takes a snapshot every `n` events, so the recovery replays at most
+    /// `n - 1` events.
+    pub fn with_snapshot_every(mut self, n: u64) -> Self {
+        self.snapshot_every = Some(n.max(1));
+        self
+    }
+
+    /// The sequence number of the last event applied.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+}
+
+impl<A, P, H, J> Handler<A, P> for PersistentActor<A, P, H, J>
+where
+    A: ActorId + Clone + Debug + Send + 'static,
+    P: Send + 'static,
+    H: Persistent<A, P>,
+    J: Journal<A, H::Event, H::Snapshot>,
+{
+    fn started(&mut self, ctx: &mut Context<A, P>) {
+        let aid = ctx.aid();
+
+        let snapshot = blocking(|| self.journal.load_snapshot(aid))
+            .unwrap_or_else(|e| panic!("Failed to load the snapshot of {aid:?} [{e}]"));
+        if let Some((seq, snapshot)) = snapshot {
+            self.handler.restore(snapshot);
+            self.seq = seq;
+        }
+
+        let events = blocking(|| self.journal.replay(aid, self.seq))
+            .unwrap_or_else(|e| panic!("Failed to replay the events of {aid:?} [{e}]"));
+        for (seq, event) in events {
+            self.handler.apply(&event);
+            self.seq = seq;
+        }
+        tracing::debug!(?aid, seq = self.seq, "recovered");
+    }
+
+    fn handle(&mut self, msg: Message<A, P>, ctx: &mut Context<A, P>) {
+        let (events, reply) = self.handler.handle_command(&msg, ctx);
+
+        for event in events {
+            let eid = MsgId::from((ctx.aid().clone(), self.seq + 1));
+            if let Err(e) = blocking(|| self.journal.append(&eid, &event)) {
+                panic!("Failed to journal the event {eid:?} [{e}]");
+            }
+            self.handler.apply(&event);
+            self.seq += 1;
+
+            if self
+                .snapshot_every
+                .is_some_and(|n| self.seq.is_multiple_of(n))
+            {
+                let snapshot = self.handler.snapshot();
+                // The events are journaled, a missing snapshot only makes
+                // the recovery longer.
+                let saved = blocking(|| self.journal.save_snapshot(ctx.aid(), self.seq, &snapshot));
+                if let Err(e) = saved {
+                    tracing::warn!(aid = ?ctx.aid(), "Failed to save the snapshot [{e}]");
+                }
+            }
+        }
+
+        if let Some(payload) = reply {
+            ctx.reply(&msg, payload);
+        }
+    }
+}
+
+/// Runs a journal call, which may block, letting a multi-threaded runtime
+/// move its other tasks off the current worker.
+fn blocking<T, F>(f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    match Handle::try_current().map(|handle| handle.runtime_flavor()) {
+        Ok(RuntimeFlavor::MultiThread) => tokio::task::block_in_place(f),
+        _ => f(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Actor, Bus, InMemoryJournal, MailboxConfig};
+    use std::time::Duration;
+    use tokio::{
+        sync::mpsc::{channel, Sender},
+        time::Instant,
+    };
+
+    /// A bank account: the commands are amounts to deposit, negative ones
+    /// withdraw. A withdrawal larger than the balance is refused.
+    struct Account {
+        balance: i64,
+        applied: Sender<i64>,
+    }
+
+    impl Persistent<u8, i64> for Account {
+        type Event = i64;
+        type Snapshot = i64;
+
+        fn handle_command(
+            &mut self,
+            msg: &Message<u8, i64>,
+            _ctx: &mut Context<u8, i64>,
+        ) -> (Vec<i64>, Option<i64>) {
+            let amount = *msg.payload();
+            if self.balance + amount < 0 {
+                return (Vec::new(), Some(self.balance));
+            }
+            (vec![amount], Some(self.balance + amount))
+        }
+
+        fn apply(&mut self, event: &i64) {
+            self.balance += event;
+            self.applied.try_send(*event).unwrap();
+        }
+
+        fn snapshot(&self) -> i64 {
+            self.balance
+        }
+
+        fn restore(&mut self, snapshot: i64) {
+            self.balance = snapshot;
+        }
+    }
+
+    type AccountJournal = InMemoryJournal<u8, i64, i64>;
+
+    async fn run(
+        bus: &mut Bus<u8, i64>,
+        journal: Arc<AccountJournal>,
+        applied: Sender<i64>,
+        commands: &[i64],
+    ) -> Vec<i64> {
+        let account = Account {
+            balance: 0,
+            applied,
+        };
+        let handler = PersistentActor::new(account, journal).with_snapshot_every(3);
+        let (actor, mut cell) = Actor::start(1, handler, MailboxConfig::default(), bus);
+        bus.add_actor(1, actor.tx()).await.unwrap();
+
+        let mut balances = Vec::new();
+        for amount in commands {
+            let reply = bus
+                .ask(Message::new(2, 1, *amount), Duration::from_secs(1))
+                .await
+                .unwrap();
+            balances.push(*reply.payload());
+        }
+
+        cell.signal_stop();
+        assert!(cell.join(Instant::now() + Duration::from_secs(1)).await);
+        balances
+    }
+
+    #[tokio::test]
+    async fn recover() {
+        let mut bus = Bus::<u8, i64>::spawn().await.unwrap();
+        let journal = Arc::new(AccountJournal::new());
+
+        let (applied_tx, mut applied_rx) = channel::<i64>(100);
+        let balances = run(&mut bus, journal.clone(), applied_tx.clone(), &[10, -20, 5]).await;
+        assert_eq!(balances, vec![10, 10, 15]);
+        assert_eq!(journal.replay(&1, 0).unwrap(), vec![(1, 10), (2, 5)]);
+
+        // the restarted actor replays the events.
+        let balances = run(&mut bus, journal.clone(), applied_tx.clone(), &[-15]).await;
+        assert_eq!(balances, vec![0]);
+        // the third event triggered a snapshot.
+        assert_eq!(journal.load_snapshot(&1).unwrap(), Some((3, 0)));
+
+        let balances = run(&mut bus, journal.clone(), applied_tx, &[1]).await;
+        assert_eq!(balances, vec![1]);
+
+        let mut applied = Vec::new();
+        while let Ok(event) = applied_rx.try_recv() {
+            applied.push(event);
+        }
+        // the last recovery started from the snapshot, without replaying.
+        assert_eq!(applied, vec![10, 5, 10, 5, -15, 1]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn multi_thread() {
+        // the journal calls run in place, on a worker the runtime gives up.
+        let mut bus = Bus::<u8, i64>::spawn().await.unwrap();
+        let journal = Arc::new(AccountJournal::new());
+
+        let (applied_tx, _applied_rx) = channel::<i64>(100);
+        let balances = run(&mut bus, journal.clone(), applied_tx.clone(), &[10, 5]).await;
+        assert_eq!(balances, vec![10, 15]);
+        let balances = run(&mut bus, journal, applied_tx, &[1]).await;
+        assert_eq!(balances, vec![16]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn no_reply_without_journaling() {
+        let mut bus = Bus::<u8, i64>::spawn().await.unwrap();
+        let journal = Arc::new(AccountJournal::new());
+
+        let (applied_tx, mut applied_rx) = channel::<i64>(100);
+        let account = Account {
+            balance: 0,
+            applied: applied_tx,
+        };
+        let handler = PersistentActor::new(account, journal.clone());
+        let (actor, _cell) = Actor::start(1, handler, MailboxConfig::default(), &bus);
+        bus.add_actor(1, actor.tx()).await.unwrap();
+
+        // another writer takes the next sequence number, the append fails.
+        let eid = MsgId::from((1, 1));
+        journal.append(&eid, &100).unwrap();
+
+        let res = bus
+            .ask(Message::new(2, 1, 10), Duration::from_secs(1))
+            .await;
+        assert!(res.is_err());
+        assert!(applied_rx.try_recv().is_err());
+        assert_eq!(journal.replay(&1, 0).unwrap(), vec![(1, 100)]);
+    }
+}
@@ -0,0 +1,412 @@
+use act_identifiers::ActorId;
+use anyhow::{anyhow, Result};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{Debug, Display},
+    future::Future,
+    hash::Hash,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::{broadcast, watch};
+
+/// How many termination notices a slow subscriber may miss before lagging.
+const MAX_TERMINATIONS: usize = 100;
+
+/// The hierarchical name of an actor, like `/user/orders/worker-3`. Each
+/// segment names a child of the path before it. The segments are made of
+/// ASCII letters, digits, `-`, `_` and `.`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ActorPath {
+    segments: Vec<String>,
+}
+
+impl ActorPath {
+    /// The path `/`, the ancestor of every other path.
+    pub fn root() -> Self {
+        Self {
+            segments: Vec::new(),
+        }
+    }
+
+    /// The path of the child with the given name.
+    pub fn child<N>(&self, name: N) -> Result<Self>
+    where
+        N: Into<String>,
+    {
+        let name = name.into();
+        check_segment(&name)?;
+        let mut segments = self.segments.clone();
+        segments.push(name);
+        Ok(Self { segments })
+    }
+
+    /// The path this one is a child of, `None` for the root.
+    pub fn parent(&self) -> Option<Self> {
+        let (_, segments) = self.segments.split_last()?;
+        Some(Self {
+            segments: segments.to_vec(),
+        })
+    }
+
+    /// The last segment, `None` for the root.
+    pub fn name(&self) -> Option<&str> {
+        self.segments.last().map(String::as_str)
+    }
+
+    /// The number of segments, 0 for the root.
+    pub fn depth(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Returns `true` if the other path is below this one.
+    pub fn is_ancestor_of(&self, other: &ActorPath) -> bool {
+        other.segments.len() > self.segments.len() && other.segments.starts_with(&self.segments)
+    }
+}
+
+fn check_segment(segment: &str) -> Result<()> {
+    let valid = segment
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if segment.is_empty() || !valid {
+        return Err(anyhow!("Invalid actor path segment [{segment:?}]"));
+    }
+    Ok(())
+}
+
+impl FromStr for ActorPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some(rest) = s.strip_prefix('/') else {
+            return Err(anyhow!("The actor path must start with '/' [{s}]"));
+        };
+        if rest.is_empty() {
+            return Ok(Self::root());
+        }
+        let segments = rest
+            .split('/')
+            .map(|segment| check_segment(segment).map(|_| segment.to_string()))
+            .collect::<Result<Vec<_>>>()
+            .map_err(|e| anyhow!("Failed to parse the actor path {s:?} [{e}]"))?;
+        Ok(Self { segments })
+    }
+}
+
+impl Display for ActorPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.segments.is_empty() {
+            return write!(f, "/");
+        }
+        for segment in &self.segments {
+            write!(f, "/{segment}")?;
+        }
+        Ok(())
+    }
+}
+
+/// How an actor task ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    /// The actor stopped, on its own or when asked to.
+    Stopped,
+    /// The actor panicked, or it was aborted because it did not stop in time.
+    Failed,
+}
+
+/// The notice published when an actor of the system terminates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Terminated<A> {
+    pub aid: A,
+    /// The path the actor was registered at, if any. It is free again.
+    pub path: Option<ActorPath>,
+    pub reason: ExitReason,
+}
+
+/// Waits for the termination of one actor, see [`crate::System::watch`].
+pub struct DeathWatch<A> {
+    aid: A,
+    exit_rx: watch::Receiver<Option<ExitReason>>,
+}
+
+impl<A> DeathWatch<A> {
+    pub fn aid(&self) -> &A {
+        &self.aid
+    }
+
+    /// Resolves once the actor terminated, right away if it already did.
+    pub async fn terminated(mut self) -> ExitReason {
+        wait_exit(&mut self.exit_rx).await
+    }
+}
+
+/// Waits until the exit reason of the actor task is published.
+pub(crate) async fn wait_exit(exit_rx: &mut watch::Receiver<Option<ExitReason>>) -> ExitReason {
+    match exit_rx.wait_for(Option::is_some).await {
+        Ok(reason) => reason.unwrap_or(ExitReason::Failed),
+        // The task is dropped before it publishes, it cannot have stopped cleanly.
+        Err(_) => ExitReason::Failed,
+    }
+}
+
+/// What the registry knows about a running actor.
+struct Entry {
+    path: Option<ActorPath>,
+    exit_rx: watch::Receiver<Option<ExitReason>>,
+}
+
+struct State<A> {
+    paths: BTreeMap<ActorPath, A>,
+    actors: HashMap<A, Entry>,
+}
+
+/// The Registry keeps track of the running actors of a system: the path
+/// each one is registered at and how to watch for its termination. An
+/// actor is removed, and its path released, as soon as it terminates.
+#[derive(Clone)]
+pub(crate) struct Registry<A> {
+    state: Arc<Mutex<State<A>>>,
+    terminated_tx: broadcast::Sender<Terminated<A>>,
+}
+
+impl<A> Registry<A>
+where
+    A: ActorId + Clone + Debug + Eq + Hash + Send + 'static,
+{
+    pub(crate) fn new() -> Self {
+        let (terminated_tx, _) = broadcast::channel(MAX_TERMINATIONS);
+        Self {
+            state: Arc::new(Mutex::new(State {
+                paths: BTreeMap::new(),
+                actors: HashMap::new(),
+            })),
+            terminated_tx,
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State<A>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Registers a running actor and watches for its termination. Fails,
+    /// without registering the actor, if the path is the root or another
+    /// actor is registered at it. Once the actor terminated, `on_exit` runs
+    /// before the actor is removed and its termination published.
+    pub(crate) fn register<F>(
+        &self,
+        aid: A,
+        path: Option<ActorPath>,
+        exit_rx: watch::Receiver<Option<ExitReason>>,
+        on_exit: F,
+    ) -> Result<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        {
+            let mut state = self.state();
+            if let Some(path) = path.as_ref() {
+                if path.is_root() {
+                    return Err(anyhow!("No actor can be registered at the root path"));
+                }
+                if let Some(owner) = state.paths.get(path).filter(|owner| **owner != aid) {
+                    return Err(anyhow!("The path {path} is taken by actor {owner:?}"));
+                }
+            }
+            if let Some(old) = state.actors.remove(&aid) {
+                // The id was reused before the previous actor terminated,
+                // e.g. by a restarted child, maybe at the same path.
+                if let Some(path) = old.path {
+                    state.paths.remove(&path);
+                }
+            }
+            if let Some(path) = path.as_ref() {
+                state.paths.insert(path.clone(), aid.clone());
+            }
+            let entry = Entry {
+                path,
+                exit_rx: exit_rx.clone(),
+            };
+            state.actors.insert(aid.clone(), entry);
+        }
+
+        let registry = self.clone();
+        let mut exit_rx = exit_rx;
+        tokio::spawn(async move {
+            let reason = wait_exit(&mut exit_rx).await;
+            on_exit.await;
+            let path = registry.remove(&aid, &exit_rx);
+            tracing::debug!(?aid, ?reason, "actor terminated");
+            let _ = registry
+                .terminated_tx
+                .send(Terminated { aid, path, reason });
+        });
+        Ok(())
+    }
+
+    /// Removes the actor, unless the id was registered again meanwhile.
+    fn remove(&self, aid: &A, exit_rx: &watch::Receiver<Option<ExitReason>>) -> Option<ActorPath> {
+        let mut state = self.state();
+        let current = state.actors.get(aid)?;
+        if !current.exit_rx.same_channel(exit_rx) {
+            return None;
+        }
+        let path = state.actors.remove(aid)?.path;
+        if let Some(path) = path.as_ref() {
+            state.paths.remove(path);
+        }
+        path
+    }
+
+    pub(crate) fn lookup(&self, path: &ActorPath) -> Option<A> {
+        self.state().paths.get(path).cloned()
+    }
+
+    pub(crate) fn path_of(&self, aid: &A) -> Option<ActorPath> {
+        self.state().actors.get(aid)?.path.clone()
+    }
+
+    /// The actors registered right below the path, ordered by path.
+    pub(crate) fn children(&self, path: &ActorPath) -> Vec<(ActorPath, A)> {
+        self.state()
+            .paths
+            .range(path.clone()..)
+            .take_while(|(child, _)| *child == path || path.is_ancestor_of(child))
+            .filter(|(child, _)| child.depth() == path.depth() + 1)
+            .map(|(child, aid)| (child.clone(), aid.clone()))
+            .collect()
+    }
+
+    pub(crate) fn watch(&self, aid: &A) -> Option<DeathWatch<A>> {
+        let state = self.state();
+        let entry = state.actors.get(aid)?;
+        Some(DeathWatch {
+            aid: aid.clone(),
+            exit_rx: entry.exit_rx.clone(),
+        })
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<Terminated<A>> {
+        self.terminated_tx.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_display() {
+        let path: ActorPath = "/user/orders/worker-3".parse().unwrap();
+        assert_eq!(path.to_string(), "/user/orders/worker-3");
+        assert_eq!(path.name(), Some("worker-3"));
+        assert_eq!(path.depth(), 3);
+
+        let root: ActorPath = "/".parse().unwrap();
+        assert!(root.is_root());
+        assert_eq!(root.to_string(), "/");
+        assert_eq!(root.name(), None);
+
+        for invalid in ["", "user", "/user/", "//user", "/user/a b", "/user/é"] {
+            assert!(invalid.parse::<ActorPath>().is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn parent_and_child() {
+        let orders: ActorPath = "/user/orders".parse().unwrap();
+        let worker = orders.child("worker-3").unwrap();
+        assert_eq!(worker.to_string(), "/user/orders/worker-3");
+        assert_eq!(worker.parent(), Some(orders.clone()));
+        assert_eq!(ActorPath::root().parent(), None);
+        assert!(orders.child("a/b").is_err());
+
+        assert!(orders.is_ancestor_of(&worker));
+        assert!(ActorPath::root().is_ancestor_of(&worker));
+        assert!(!worker.is_ancestor_of(&orders));
+        assert!(!orders.is_ancestor_of(&orders));
+        let ordersx: ActorPath = "/user/ordersx/worker".parse().unwrap();
+        assert!(!orders.is_ancestor_of(&ordersx));
+    }
+
+    #[tokio::test]
+    async fn children_and_removal() {
+        let registry = Registry::<u8>::new();
+        let mut terminated_rx = registry.subscribe();
+
+        let user: ActorPath = "/user".parse().unwrap();
+        let mut exits = Vec::new();
+        for (aid, path) in [
+            (1, "/user"),
+            (2, "/user/orders"),
+            (3, "/user/orders/worker-1"),
+            (4, "/user/payments"),
+            (5, "/user-admin"),
+        ] {
+            let path: ActorPath = path.parse().unwrap();
+            let (exit_tx, exit_rx) = watch::channel(None);
+            registry
+                .register(aid, Some(path), exit_rx, async {})
+                .unwrap();
+            exits.push(exit_tx);
+        }
+
+        // a path taken by another actor, or the root, is refused.
+        for path in [user.clone(), ActorPath::root()] {
+            let (_exit_tx, exit_rx) = watch::channel(None);
+            assert!(registry.register(6, Some(path), exit_rx, async {}).is_err());
+        }
+        assert!(registry.watch(&6).is_none());
+        assert_eq!(registry.lookup(&user), Some(1));
+
+        let children = registry.children(&user);
+        let aids = children.iter().map(|(_, aid)| *aid).collect::<Vec<_>>();
+        assert_eq!(aids, vec![2, 4]);
+        assert_eq!(registry.lookup(&"/user/orders".parse().unwrap()), Some(2));
+        assert_eq!(
+            registry.path_of(&4),
+            Some("/user/payments".parse().unwrap())
+        );
+
+        // the orders actor terminates, its path is released.
+        let watch = registry.watch(&2).unwrap();
+        exits[1].send_replace(Some(ExitReason::Failed));
+        assert_eq!(watch.terminated().await, ExitReason::Failed);
+
+        let terminated = terminated_rx.recv().await.unwrap();
+        assert_eq!(terminated.aid, 2);
+        assert_eq!(terminated.path, Some("/user/orders".parse().unwrap()));
+        assert_eq!(terminated.reason, ExitReason::Failed);
+        assert_eq!(registry.lookup(&"/user/orders".parse().unwrap()), None);
+        assert!(registry.watch(&2).is_none());
+    }
+
+    #[tokio::test]
+    async fn register_again() {
+        let registry = Registry::<u8>::new();
+        let mut terminated_rx = registry.subscribe();
+        let path: ActorPath = "/user/worker".parse().unwrap();
+
+        let (first_tx, first_rx) = watch::channel(None);
+        registry
+            .register(1, Some(path.clone()), first_rx, async {})
+            .unwrap();
+        // a restarted actor registers again before the first one is removed.
+        let (_second_tx, second_rx) = watch::channel(None);
+        registry
+            .register(1, Some(path.clone()), second_rx, async {})
+            .unwrap();
+        assert_eq!(registry.lookup(&path), Some(1));
+
+        // the termination of the first one does not release the path.
+        first_tx.send_replace(Some(ExitReason::Failed));
+        let terminated = terminated_rx.recv().await.unwrap();
+        assert_eq!(terminated.path, None);
+        assert_eq!(registry.lookup(&path), Some(1));
+        assert_eq!(registry.path_of(&1), Some(path));
+    }
+}
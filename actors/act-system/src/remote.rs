@@ -0,0 +1,357 @@
+use crate::{
+    actor::spawn_watched,
+    mailbox,
+    wire::{encode_frame, read_frame},
+    Bus, DeadLetterReason, ExitReason, MailboxConfig, Message, Wire,
+};
+use act_identifiers::{ActorId, Binary, DecodeIdError};
+use anyhow::{anyhow, Result};
+use std::{
+    fmt::{Debug, Display},
+    hash::Hash,
+    marker::PhantomData,
+    net::SocketAddr,
+    time::Duration,
+};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        mpsc::{channel, Sender},
+        watch,
+    },
+    task::JoinHandle,
+};
+
+/// How many messages can wait to be written to a peer connection.
+const PEER_CAPACITY: usize = 100;
+
+/// How long the node waits before accepting again after a failure, e.g.
+/// when it ran out of file descriptors.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The identifier of a node, i.e. of a process running a bus.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(pub u32);
+
+impl Display for NodeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "node-{}", self.0)
+    }
+}
+
+impl Binary for NodeId {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        Binary::encode(&self.0, buf);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self, DecodeIdError> {
+        <u32 as Binary>::decode(buf).map(Self)
+    }
+}
+
+/// An actor id qualified by the node the actor lives on, which keeps the
+/// ids of actors living on different nodes apart. The same id is used
+/// to address the actor whether the sender is on its node or not.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeActorId<A> {
+    node: NodeId,
+    aid: A,
+}
+
+impl<A> NodeActorId<A> {
+    pub fn new(node: NodeId, aid: A) -> Self {
+        Self { node, aid }
+    }
+
+    pub fn node(&self) -> NodeId {
+        self.node
+    }
+
+    pub fn aid(&self) -> &A {
+        &self.aid
+    }
+}
+
+impl<A> Display for NodeActorId<A>
+where
+    A: Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.node, self.aid)
+    }
+}
+
+impl<A> Binary for NodeActorId<A>
+where
+    A: Binary,
+{
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.node.encode(buf);
+        self.aid.encode(buf);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self, DecodeIdError> {
+        let node = NodeId::decode(buf)?;
+        let aid = A::decode(buf)?;
+        Ok(Self { node, aid })
+    }
+}
+
+/// A Node accepts connections from peers and routes the messages they
+/// send through the local bus.
+pub struct Node {
+    local_addr: SocketAddr,
+    handle: JoinHandle<()>,
+}
+
+impl Node {
+    /// Listens for peers on the given address. Use port 0 to let the
+    /// operating system pick one, see [`Node::local_addr`].
+    pub async fn listen<S, A, P>(addr: S, bus: &Bus<A, P>) -> Result<Self>
+    where
+        S: ToSocketAddrs,
+        A: ActorId + Clone + Debug + Binary + Send + 'static,
+        P: Wire + Send + 'static,
+    {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| anyhow!("Failed to bind the node listener [{e}]"))?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|e| anyhow!("Failed to get the node address [{e}]"))?;
+
+        let bus_tx = bus.bus_tx();
+        let handle = tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _addr)) => stream,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "failed to accept a peer");
+                        tokio::time::sleep(ACCEPT_BACKOFF).await;
+                        continue;
+                    }
+                };
+                let bus_tx = bus_tx.clone();
+                tokio::spawn(async move {
+                    let mut stream = stream;
+                    // A broken or malformed connection is dropped.
+                    while let Ok(Some(msg)) = read_frame::<_, Message<A, P>>(&mut stream).await {
+                        if bus_tx.send(msg).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        Ok(Self { local_addr, handle })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stops accepting peers. The connections already accepted are kept.
+    pub fn stop(&self) {
+        self.handle.abort();
+    }
+}
+
+/// A Peer is a connection to a remote node. The actors living on the
+/// remote node are registered with the local bus through the peer, so
+/// the local actors send them messages as if they were local.
+pub struct Peer<A, P>
+where
+    A: ActorId + Debug,
+{
+    /// The frames of the messages to write to the connection.
+    frames_tx: Sender<Vec<u8>>,
+    msgs: PhantomData<fn(Message<A, P>)>,
+}
+
+impl<A, P> Peer<A, P>
+where
+    A: ActorId + Clone + Debug + Hash + Binary + Send + 'static,
+    P: Wire + Send + 'static,
+{
+    /// Connects to the node listening on the given address.
+    pub async fn connect<S>(addr: S) -> Result<Self>
+    where
+        S: ToSocketAddrs,
+    {
+        let stream = TcpStream::connect(addr)
+            .await
+            .map_err(|e| anyhow!("Failed to connect to the node [{e}]"))?;
+        // The writer task batches the messages, do not delay them further.
+        let _ = stream.set_nodelay(true);
+
+        let (frames_tx, mut frames_rx) = channel::<Vec<u8>>(PEER_CAPACITY);
+        tokio::spawn(async move {
+            let mut writer = stream;
+            while let Some(mut frames) = frames_rx.recv().await {
+                // Batch the messages which are already waiting in one write.
+                while let Ok(frame) = frames_rx.try_recv() {
+                    frames.extend_from_slice(&frame);
+                }
+                if writer.write_all(&frames).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            frames_tx,
+            msgs: PhantomData,
+        })
+    }
+
+    /// Registers with the bus an actor living on the remote node. The
+    /// messages the bus routes to the actor are sent to the remote node,
+    /// those too large for the remote node to accept go to the dead-letter
+    /// office.
+    /// When the connection is lost the actor mailbox is closed, so the bus
+    /// stops routing to it.
+    pub async fn register(&self, aid: A, bus: &mut Bus<A, P>) -> Result<()> {
+        self.register_watched(aid, bus).await.map(|_| ())
+    }
+
+    /// Registers the remote actor like [`Peer::register`], returning the
+    /// forwarding task and a receiver which tells when the connection is lost.
+    pub(crate) async fn register_watched(
+        &self,
+        aid: A,
+        bus: &mut Bus<A, P>,
+    ) -> Result<(JoinHandle<()>, watch::Receiver<Option<ExitReason>>)> {
+        let (tx, rx) = mailbox::<A, P>(MailboxConfig::default());
+        let mut rx = rx.with_dead_letters(bus);
+        bus.add_actor(aid, tx).await?;
+
+        let frames_tx = self.frames_tx.clone();
+        let bus = bus.clone();
+        Ok(spawn_watched(async move {
+            while let Some(msg) = rx.recv().await {
+                let frame = match encode_frame(&msg) {
+                    Ok(frame) => frame,
+                    Err(_) => {
+                        bus.dead_letter(msg, DeadLetterReason::TooLarge);
+                        continue;
+                    }
+                };
+                if frames_tx.send(frame).await.is_err() {
+                    break;
+                }
+            }
+        }))
+    }
+
+    /// Returns `true` once the connection to the remote node was lost.
+    pub fn is_closed(&self) -> bool {
+        self.frames_tx.is_closed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Actor, Context, MAX_FRAME_LEN};
+    use std::time::Duration;
+
+    type Aid = NodeActorId<u32>;
+
+    #[test]
+    fn node_actor_id() {
+        let aid = NodeActorId::new(NodeId(1), 10_u32);
+        assert_eq!(aid.node(), NodeId(1));
+        assert_eq!(aid.aid(), &10);
+        assert_eq!(aid.to_string(), "node-1/10");
+
+        let mut buf = Vec::new();
+        aid.encode(&mut buf);
+        assert_eq!(Aid::decode(&mut buf.as_slice()).unwrap(), aid);
+    }
+
+    #[tokio::test]
+    async fn ask_remote_actor() {
+        let client = NodeActorId::new(NodeId(1), 1);
+        let echo = NodeActorId::new(NodeId(2), 10);
+
+        // node 2 runs the echo actor.
+        let mut bus2 = Bus::<Aid, String>::spawn().await.unwrap();
+        let actor = Actor::spawn(
+            echo,
+            |msg: Message<Aid, String>, ctx: &mut Context<Aid, String>| {
+                let payload = format!("echo {}", msg.payload());
+                ctx.reply(&msg, payload);
+            },
+            &bus2,
+        )
+        .await
+        .unwrap();
+        bus2.add_actor(echo, actor.tx()).await.unwrap();
+        let node2 = Node::listen("127.0.0.1:0", &bus2).await.unwrap();
+
+        // node 1 knows the echo actor through a peer, and node 2 knows the
+        // client through another peer, so the reply finds its way back.
+        let mut bus1 = Bus::<Aid, String>::spawn().await.unwrap();
+        let node1 = Node::listen("127.0.0.1:0", &bus1).await.unwrap();
+
+        let peer2 = Peer::connect(node2.local_addr()).await.unwrap();
+        peer2.register(echo, &mut bus1).await.unwrap();
+        let peer1 = Peer::connect(node1.local_addr()).await.unwrap();
+        peer1.register(client, &mut bus2).await.unwrap();
+
+        let reply = bus1
+            .ask(
+                Message::new(client, echo, "hello".to_string()),
+                Duration::from_secs(1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(reply.fid().as_ref(), &echo);
+        assert_eq!(reply.payload(), "echo hello");
+
+        node1.stop();
+        node2.stop();
+        bus1.stop().await.unwrap();
+        bus2.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn message_too_large() {
+        let client = NodeActorId::new(NodeId(1), 1);
+        let remote = NodeActorId::new(NodeId(2), 10);
+
+        let mut bus2 = Bus::<Aid, String>::spawn().await.unwrap();
+        let node2 = Node::listen("127.0.0.1:0", &bus2).await.unwrap();
+        let mut bus1 = Bus::<Aid, String>::spawn().await.unwrap();
+        let peer2 = Peer::connect(node2.local_addr()).await.unwrap();
+        peer2.register(remote, &mut bus1).await.unwrap();
+
+        // the ask fails right away, the message never leaves the node.
+        let payload = "x".repeat(MAX_FRAME_LEN);
+        let err = bus1
+            .ask(
+                Message::new(client, remote, payload),
+                Duration::from_secs(10),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("too large"), "{err}");
+        let dead_letters = bus1.dead_letters().await.unwrap();
+        assert_eq!(dead_letters[0].reason, DeadLetterReason::TooLarge);
+
+        node2.stop();
+        bus1.stop().await.unwrap();
+        bus2.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn connect_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        assert!(Peer::<Aid, ()>::connect(addr).await.is_err());
+    }
+}
@@ -0,0 +1,1022 @@
+use crate::{
+    actor::spawn_watched, mailbox, shutdown::stop_actors, Actor, ActorCell, Bus, DeadLetterReason,
+    ExitReason, Handler, MailboxConfig, MailboxError, MailboxReceiver, MailboxSender, Message,
+    OverflowPolicy, SendResult, ShutdownReport,
+};
+use act_identifiers::ActorId;
+use anyhow::{anyhow, Result};
+use std::{
+    collections::{hash_map::DefaultHasher, hash_map::RandomState, BTreeMap},
+    fmt::Debug,
+    future::Future,
+    hash::{BuildHasher, Hash, Hasher},
+    pin::Pin,
+    time::Duration,
+};
+use tokio::{
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        oneshot, watch,
+    },
+    time::Instant,
+};
+
+/// The number of points each worker gets on the consistent hashing ring.
+const VIRTUAL_NODES: u64 = 64;
+
+/// How long a worker removed from the pool may take to drain its mailbox.
+const WORKER_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Hashes the routing key of a payload.
+type KeyFn<P> = Box<dyn Fn(&P) -> u64 + Send + Sync>;
+
+/// Starts a new worker with the router id, registered with the given bus.
+type StartFn<A, P> =
+    Box<dyn Fn(A, MailboxConfig, &Bus<A, P>) -> (Actor<A, P>, ActorCell<A>) + Send + Sync>;
+
+/// A message waiting for room in the mailbox of the worker with the given
+/// number, which it resolves to along with the outcome of the send.
+type PendingSend<A, P> = Pin<Box<dyn Future<Output = (u64, SendResult<A, P>)> + Send>>;
+
+/// How a router picks the worker a message is passed to.
+pub struct Routing<P> {
+    strategy: Strategy<P>,
+}
+
+enum Strategy<P> {
+    RoundRobin {
+        next: usize,
+    },
+    Random {
+        rng: XorShift,
+    },
+    SmallestMailbox,
+    ConsistentHash {
+        key: KeyFn<P>,
+        ring: BTreeMap<u64, u64>,
+    },
+}
+
+impl<P> Routing<P> {
+    /// Passes the messages to the workers in turn.
+    pub fn round_robin() -> Self {
+        Self {
+            strategy: Strategy::RoundRobin { next: 0 },
+        }
+    }
+
+    /// Passes each message to a worker picked at random.
+    pub fn random() -> Self {
+        Self {
+            strategy: Strategy::Random {
+                rng: XorShift::new(),
+            },
+        }
+    }
+
+    /// Passes each message to the worker with the fewest messages waiting
+    /// in its mailbox, the first one in the pool on a tie.
+    pub fn smallest_mailbox() -> Self {
+        Self {
+            strategy: Strategy::SmallestMailbox,
+        }
+    }
+
+    /// Passes the messages with the same key to the same worker, as long
+    /// as the pool does not change. When it is resized only the keys of
+    /// the workers added or removed move to another worker.
+    pub fn consistent_hash<F, K>(key: F) -> Self
+    where
+        F: Fn(&P) -> K + Send + Sync + 'static,
+        K: Hash,
+    {
+        Self {
+            strategy: Strategy::ConsistentHash {
+                key: Box::new(move |payload| hash(&key(payload))),
+                ring: BTreeMap::new(),
+            },
+        }
+    }
+
+    /// Whether a message must go to the worker selected for it, even when
+    /// that worker is full, rather than to another one with room.
+    fn is_sticky(&self) -> bool {
+        matches!(self.strategy, Strategy::ConsistentHash { .. })
+    }
+
+    /// Rebuilds the ring from the ids of the workers in the pool.
+    fn rebuild(&mut self, workers: &[u64]) {
+        if let Strategy::ConsistentHash { ring, .. } = &mut self.strategy {
+            ring.clear();
+            for id in workers {
+                for replica in 0..VIRTUAL_NODES {
+                    ring.insert(hash(&(id, replica)), *id);
+                }
+            }
+        }
+    }
+
+    /// Returns the index of the worker, among the given ones, the payload
+    /// is passed to. There must be at least one worker.
+    fn select<A>(&mut self, payload: &P, workers: &[Worker<A, P>]) -> usize
+    where
+        A: ActorId + Debug,
+    {
+        match &mut self.strategy {
+            Strategy::RoundRobin { next } => {
+                let index = *next % workers.len();
+                *next = index + 1;
+                index
+            }
+            Strategy::Random { rng } => (rng.next() % workers.len() as u64) as usize,
+            Strategy::SmallestMailbox => workers
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, worker)| worker.tx.len())
+                .map(|(index, _)| index)
+                .unwrap_or(0),
+            Strategy::ConsistentHash { key, ring } => {
+                let point = key(payload);
+                let id = ring
+                    .range(point..)
+                    .next()
+                    .or_else(|| ring.iter().next())
+                    .map(|(_, id)| *id);
+                id.and_then(|id| workers.iter().position(|worker| worker.id == id))
+                    .unwrap_or(0)
+            }
+        }
+    }
+}
+
+fn hash<T>(value: &T) -> u64
+where
+    T: Hash + ?Sized,
+{
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A xorshift generator, good enough to spread messages over workers.
+struct XorShift(u64);
+
+impl XorShift {
+    fn new() -> Self {
+        // The hasher keys are random, so is the seed. It must not be zero.
+        Self(RandomState::new().build_hasher().finish() | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+/// The Resizer grows and shrinks the pool of a router with the load. A
+/// worker is busy when at least `pressure_threshold` messages wait in its
+/// mailbox. Every `interval` messages, the pool grows by one worker if
+/// all the workers are busy and it shrinks by one if less than half are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resizer {
+    lower: usize,
+    upper: usize,
+    pressure_threshold: usize,
+    interval: usize,
+}
+
+impl Resizer {
+    /// Keeps between `lower` and `upper` workers in the pool.
+    pub fn new(lower: usize, upper: usize) -> Self {
+        assert!(lower > 0, "the pool must keep at least one worker");
+        assert!(
+            lower <= upper,
+            "the lower bound must not exceed the upper bound"
+        );
+        Self {
+            lower,
+            upper,
+            pressure_threshold: 1,
+            interval: 10,
+        }
+    }
+
+    pub fn with_pressure_threshold(mut self, messages: usize) -> Self {
+        self.pressure_threshold = messages.max(1);
+        self
+    }
+
+    pub fn with_interval(mut self, messages: usize) -> Self {
+        self.interval = messages.max(1);
+        self
+    }
+
+    fn clamp(&self, size: usize) -> usize {
+        size.clamp(self.lower, self.upper)
+    }
+
+    /// The size the pool should have given the mailbox lengths of its workers.
+    fn target<I>(&self, lens: I) -> usize
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        let (mut size, mut busy) = (0, 0);
+        for len in lens {
+            size += 1;
+            if len >= self.pressure_threshold {
+                busy += 1;
+            }
+        }
+        if busy == size {
+            self.clamp(size + 1)
+        } else if busy * 2 < size {
+            self.clamp(size - 1)
+        } else {
+            self.clamp(size)
+        }
+    }
+}
+
+/// The RouterSpec describes the pool of identical workers a router fronts.
+/// The factory is called every time a worker is started.
+pub struct RouterSpec<A, P>
+where
+    A: ActorId + Debug,
+{
+    routing: Routing<P>,
+    workers: usize,
+    mailbox: MailboxConfig,
+    resizer: Option<Resizer>,
+    start: StartFn<A, P>,
+}
+
+impl<A, P> RouterSpec<A, P>
+where
+    A: ActorId + Clone + Debug + Send + 'static,
+    P: Send + 'static,
+{
+    pub fn new<F, H>(routing: Routing<P>, factory: F) -> Self
+    where
+        F: Fn() -> H + Send + Sync + 'static,
+        H: Handler<A, P>,
+    {
+        let start = Box::new(move |aid: A, config: MailboxConfig, bus: &Bus<A, P>| {
+            Actor::start(aid, factory(), config, bus)
+        });
+        Self {
+            routing,
+            workers: 1,
+            mailbox: MailboxConfig::default(),
+            resizer: None,
+            start,
+        }
+    }
+
+    /// The number of workers started with the router, at least one.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Every worker gets a mailbox built from the configuration.
+    pub fn with_mailbox(mut self, config: MailboxConfig) -> Self {
+        self.mailbox = config;
+        self
+    }
+
+    /// Resizes the pool with the load. The initial number of workers is
+    /// brought within the bounds of the resizer.
+    pub fn with_resizer(mut self, resizer: Resizer) -> Self {
+        self.resizer = Some(resizer);
+        self
+    }
+}
+
+/// The control messages the router task accepts.
+enum RouterCtrl<A> {
+    Size(oneshot::Sender<usize>),
+    Resize(usize),
+    Stop(Instant, oneshot::Sender<ShutdownReport<A>>),
+}
+
+/// Identifies a worker in the shutdown report. The workers all run
+/// with the router id, they are told apart by their number: they are
+/// numbered from one in the order they are started, and a worker started
+/// to replace another one gets a new number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WorkerId<A> {
+    pub router: A,
+    pub worker: u64,
+}
+
+/// The handle of a running router. The router is registered with the bus
+/// under its own id and passes each message sent to that id to one of its
+/// workers, unchanged: the workers reply to the original sender. The
+/// workers act on behalf of the router, they run with the router id.
+///
+/// The router never waits on a full worker while another one has room,
+/// except with [`Routing::consistent_hash`] which keeps each key on its
+/// worker. When the mailboxes block and the workers are all full, the
+/// router holds the message until there is room, taking no other one in
+/// meanwhile but still answering to the size, resize and stop requests.
+pub struct Router<A> {
+    aid: A,
+    ctrl_tx: Sender<RouterCtrl<A>>,
+    /// Receives how the router task ended, once it did.
+    pub(crate) exit_rx: watch::Receiver<Option<ExitReason>>,
+}
+
+impl<A> Clone for Router<A>
+where
+    A: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            aid: self.aid.clone(),
+            ctrl_tx: self.ctrl_tx.clone(),
+            exit_rx: self.exit_rx.clone(),
+        }
+    }
+}
+
+impl<A> Router<A> {
+    pub fn aid(&self) -> &A {
+        &self.aid
+    }
+
+    /// The number of workers in the pool.
+    pub async fn size(&self) -> Result<usize> {
+        let (size_tx, size_rx) = oneshot::channel::<usize>();
+        self.send_ctrl(RouterCtrl::Size(size_tx)).await?;
+        size_rx
+            .await
+            .map_err(|e| anyhow!("Failed to get the size of the pool [{e}]"))
+    }
+
+    /// Grows or shrinks the pool to the given number of workers, at least
+    /// one and within the bounds of the resizer if there is one. The
+    /// workers removed handle the messages in their mailbox before stopping.
+    pub async fn resize(&self, workers: usize) -> Result<()> {
+        self.send_ctrl(RouterCtrl::Resize(workers)).await
+    }
+
+    /// Passes the messages already received to the workers, then stops
+    /// the workers and the router. The workers still running after the
+    /// timeout are aborted. The report lists the workers only, see
+    /// [`ShutdownReport::workers_stopped`]. Fails if the router already stopped.
+    pub async fn stop(&self, timeout: Duration) -> Result<ShutdownReport<A>> {
+        let (report_tx, report_rx) = oneshot::channel::<ShutdownReport<A>>();
+        let deadline = Instant::now() + timeout;
+        self.send_ctrl(RouterCtrl::Stop(deadline, report_tx))
+            .await?;
+        report_rx
+            .await
+            .map_err(|e| anyhow!("Failed to get the shutdown report [{e}]"))
+    }
+
+    async fn send_ctrl(&self, msg: RouterCtrl<A>) -> Result<()> {
+        self.ctrl_tx
+            .send(msg)
+            .await
+            .map_err(|_e| anyhow!("The router is not running"))
+    }
+}
+
+/// A worker of the pool.
+struct Worker<A, P>
+where
+    A: ActorId + Debug,
+{
+    /// Numbers the worker in the report and on the consistent hashing ring.
+    id: u64,
+    tx: MailboxSender<A, P>,
+    cell: ActorCell<A>,
+}
+
+impl<A, P> Worker<A, P>
+where
+    A: ActorId + Debug,
+{
+    /// The cell of the worker, identified by its number.
+    fn into_cell(self) -> ActorCell<WorkerId<A>> {
+        let ActorCell {
+            aid,
+            stop_tx,
+            handle,
+            exit_rx,
+        } = self.cell;
+        ActorCell {
+            aid: WorkerId {
+                router: aid,
+                worker: self.id,
+            },
+            stop_tx,
+            handle,
+            exit_rx,
+        }
+    }
+}
+
+/// Everything the router task owns.
+struct Pool<A, P>
+where
+    A: ActorId + Debug,
+{
+    aid: A,
+    routing: Routing<P>,
+    mailbox: MailboxConfig,
+    resizer: Option<Resizer>,
+    start: StartFn<A, P>,
+    workers: Vec<Worker<A, P>>,
+    next_id: u64,
+    routed: usize,
+    bus: Bus<A, P>,
+}
+
+impl<A, P> Pool<A, P>
+where
+    A: ActorId + Clone + Debug + Send + 'static,
+    P: Send + 'static,
+{
+    fn start_worker(&mut self) -> Worker<A, P> {
+        let (actor, cell) = (self.start)(self.aid.clone(), self.mailbox, &self.bus);
+        self.next_id += 1;
+        Worker {
+            id: self.next_id,
+            tx: actor.tx(),
+            cell,
+        }
+    }
+
+    fn resize(&mut self, size: usize) {
+        let size = match self.resizer {
+            Some(resizer) => resizer.clamp(size),
+            None => size.max(1),
+        };
+        if size == self.workers.len() {
+            return;
+        }
+        tracing::debug!(aid = ?self.aid, from = self.workers.len(), to = size, "resizing");
+
+        while self.workers.len() < size {
+            let worker = self.start_worker();
+            self.workers.push(worker);
+        }
+        while self.workers.len() > size {
+            if let Some(worker) = self.workers.pop() {
+                Self::retire(worker);
+            }
+        }
+        self.rebuild();
+    }
+
+    /// Stops the worker once it handled the messages in its mailbox.
+    fn retire(worker: Worker<A, P>) {
+        let mut cell = worker.cell;
+        cell.signal_stop();
+        tokio::spawn(async move {
+            cell.join(Instant::now() + WORKER_STOP_TIMEOUT).await;
+        });
+    }
+
+    fn rebuild(&mut self) {
+        let ids = self
+            .workers
+            .iter()
+            .map(|worker| worker.id)
+            .collect::<Vec<_>>();
+        self.routing.rebuild(&ids);
+    }
+
+    /// Replaces the worker which stopped or panicked.
+    fn replace(&mut self, index: usize) {
+        tracing::debug!(aid = ?self.aid, "replacing a worker");
+        self.workers[index] = self.start_worker();
+        self.rebuild();
+    }
+
+    /// Passes the message to a worker without waiting. When the workers
+    /// which may take it are full and their mailboxes block, returns the
+    /// send which waits for room in the worker selected for the message.
+    fn route(&mut self, msg: Message<A, P>) -> Option<PendingSend<A, P>> {
+        // The pressure is measured when a message comes in, so the one
+        // being routed does not count.
+        if let Some(resizer) = self.resizer {
+            if self.routed.is_multiple_of(resizer.interval) {
+                let target = resizer.target(self.workers.iter().map(|worker| worker.tx.len()));
+                self.resize(target);
+            }
+        }
+        self.routed += 1;
+
+        self.dispatch(msg)
+    }
+
+    fn dispatch(&mut self, mut msg: Message<A, P>) -> Option<PendingSend<A, P>> {
+        'select: loop {
+            let index = self.routing.select(msg.payload(), &self.workers);
+            // The selected worker first, then the others in turn.
+            let tries = if self.routing.is_sticky() {
+                1
+            } else {
+                self.workers.len()
+            };
+            for offset in 0..tries {
+                let at = (index + offset) % self.workers.len();
+                match self.workers[at].tx.try_send(msg) {
+                    Ok(discarded) => {
+                        self.discarded(discarded);
+                        return None;
+                    }
+                    Err(MailboxError::Full(full)) => msg = full,
+                    Err(MailboxError::Closed(closed)) => {
+                        self.replace(at);
+                        msg = closed;
+                        continue 'select;
+                    }
+                }
+            }
+
+            if self.mailbox.overflow() != OverflowPolicy::Block {
+                self.bus.dead_letter(msg, DeadLetterReason::MailboxFull);
+                return None;
+            }
+            let worker = &self.workers[index];
+            let (id, tx) = (worker.id, worker.tx.clone());
+            return Some(Box::pin(async move { (id, tx.send(msg).await) }));
+        }
+    }
+
+    /// Handles the outcome of a send which waited for room in a worker.
+    fn sent(&mut self, id: u64, result: SendResult<A, P>) -> Option<PendingSend<A, P>> {
+        match result {
+            Ok(discarded) => {
+                self.discarded(discarded);
+                None
+            }
+            Err(MailboxError::Full(full)) => {
+                self.bus.dead_letter(full, DeadLetterReason::MailboxFull);
+                None
+            }
+            Err(MailboxError::Closed(closed)) => {
+                // The worker may have left the pool since.
+                if let Some(index) = self.workers.iter().position(|worker| worker.id == id) {
+                    self.replace(index);
+                }
+                self.dispatch(closed)
+            }
+        }
+    }
+
+    /// Waits until the message is passed to a worker.
+    async fn flush(&mut self, mut pending: Option<PendingSend<A, P>>) {
+        while let Some(send) = pending {
+            let (id, result) = send.await;
+            pending = self.sent(id, result);
+        }
+    }
+
+    fn discarded(&self, discarded: Option<Message<A, P>>) {
+        if let Some(discarded) = discarded {
+            self.bus.dead_letter(discarded, DeadLetterReason::Discarded);
+        }
+    }
+}
+
+impl<A> Router<A>
+where
+    A: ActorId + Clone + Debug + Send + 'static,
+{
+    /// Starts the workers and registers the router with the bus.
+    pub(crate) async fn spawn<P>(aid: A, spec: RouterSpec<A, P>, mut bus: Bus<A, P>) -> Result<Self>
+    where
+        P: Send + 'static,
+    {
+        let (ctrl_tx, ctrl_rx) = channel::<RouterCtrl<A>>(10);
+        let (inbound_tx, inbound_rx) = mailbox::<A, P>(MailboxConfig::default());
+        let inbound_rx = inbound_rx.with_dead_letters(&bus);
+
+        let RouterSpec {
+            routing,
+            workers,
+            mailbox,
+            resizer,
+            start,
+        } = spec;
+
+        let mut pool = Pool {
+            aid: aid.clone(),
+            routing,
+            mailbox,
+            resizer,
+            start,
+            workers: Vec::new(),
+            next_id: 0,
+            routed: 0,
+            bus: bus.clone(),
+        };
+        pool.resize(workers);
+
+        bus.add_actor(aid.clone(), inbound_tx).await?;
+        let (_handle, exit_rx) = spawn_watched(Self::internal_loop(pool, inbound_rx, ctrl_rx));
+
+        Ok(Self {
+            aid,
+            ctrl_tx,
+            exit_rx,
+        })
+    }
+
+    async fn internal_loop<P>(
+        mut pool: Pool<A, P>,
+        mut inbound_rx: MailboxReceiver<A, P>,
+        mut ctrl_rx: Receiver<RouterCtrl<A>>,
+    ) where
+        P: Send + 'static,
+    {
+        // The message waiting for room in a full worker, if any.
+        let mut pending: Option<PendingSend<A, P>> = None;
+        loop {
+            tokio::select! {
+                biased;
+
+                msg = ctrl_rx.recv() => match msg {
+                    Some(RouterCtrl::Size(size_tx)) => {
+                        let _ = size_tx.send(pool.workers.len());
+                    }
+                    Some(RouterCtrl::Resize(size)) => pool.resize(size),
+                    Some(RouterCtrl::Stop(deadline, report_tx)) => {
+                        // Refuse any new message, but route the ones already received.
+                        inbound_rx.close();
+                        pool.flush(pending.take()).await;
+                        while let Some(msg) = inbound_rx.recv().await {
+                            let pending = pool.route(msg);
+                            pool.flush(pending).await;
+                        }
+                        let cells = pool.workers.drain(..).map(Worker::into_cell).collect();
+                        let workers = stop_actors(cells, deadline).await;
+                        let _ = report_tx.send(ShutdownReport {
+                            workers_stopped: workers.stopped,
+                            workers_timed_out: workers.timed_out,
+                            ..ShutdownReport::default()
+                        });
+                        return;
+                    }
+                    None => break, // all the handles have dropped
+                },
+                (id, result) = wait(&mut pending), if pending.is_some() => {
+                    pending = pool.sent(id, result);
+                }
+                // No other message is taken in while one waits for room.
+                msg = inbound_rx.recv(), if pending.is_none() => match msg {
+                    Some(msg) => pending = pool.route(msg),
+                    None => break,
+                },
+            }
+        }
+
+        for worker in pool.workers.drain(..) {
+            Pool::retire(worker);
+        }
+    }
+}
+
+/// Waits for the pending send, forever if there is none.
+async fn wait<A, P>(pending: &mut Option<PendingSend<A, P>>) -> (u64, SendResult<A, P>)
+where
+    A: ActorId + Debug,
+{
+    match pending {
+        Some(send) => send.await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Context;
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+    /// A worker which replies with its own number.
+    struct Numbered(u32);
+
+    impl Handler<u8, u32> for Numbered {
+        fn handle(&mut self, msg: Message<u8, u32>, ctx: &mut Context<u8, u32>) {
+            ctx.reply(&msg, self.0);
+        }
+    }
+
+    /// Numbers the workers in the order they are started.
+    fn numbered() -> impl Fn() -> Numbered + Send + Sync + 'static {
+        let next = Arc::new(AtomicU32::new(0));
+        move || Numbered(next.fetch_add(1, Ordering::SeqCst))
+    }
+
+    async fn ask(bus: &Bus<u8, u32>, payload: u32) -> u32 {
+        bus.ask(Message::new(1, 10, payload), Duration::from_secs(1))
+            .await
+            .unwrap()
+            .into_payload()
+    }
+
+    #[tokio::test]
+    async fn round_robin() {
+        let bus = Bus::<u8, u32>::spawn().await.unwrap();
+        let spec = RouterSpec::new(Routing::round_robin(), numbered()).with_workers(3);
+        let router = Router::spawn(10, spec, bus.clone()).await.unwrap();
+
+        let mut numbers = Vec::new();
+        for payload in 0..6 {
+            numbers.push(ask(&bus, payload).await);
+        }
+        assert_eq!(numbers, vec![0, 1, 2, 0, 1, 2]);
+
+        let report = router.stop(Duration::from_secs(1)).await.unwrap();
+        let stopped = report
+            .workers_stopped
+            .iter()
+            .map(|worker| (worker.router, worker.worker))
+            .collect::<Vec<_>>();
+        assert_eq!(stopped, vec![(10, 3), (10, 2), (10, 1)]);
+        assert!(router.size().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn random() {
+        let bus = Bus::<u8, u32>::spawn().await.unwrap();
+        let spec = RouterSpec::new(Routing::random(), numbered()).with_workers(2);
+        let router = Router::spawn(10, spec, bus.clone()).await.unwrap();
+
+        let mut seen = [false; 2];
+        for payload in 0..64 {
+            seen[ask(&bus, payload).await as usize] = true;
+        }
+        assert_eq!(seen, [true, true]);
+
+        router.stop(Duration::from_secs(1)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn consistent_hash() {
+        let bus = Bus::<u8, u32>::spawn().await.unwrap();
+        let spec = RouterSpec::new(
+            Routing::consistent_hash(|payload: &u32| payload % 8),
+            numbered(),
+        )
+        .with_workers(4);
+        let router = Router::spawn(10, spec, bus.clone()).await.unwrap();
+
+        let mut owners = Vec::new();
+        for key in 0..8 {
+            owners.push(ask(&bus, key).await);
+        }
+        // the same key goes to the same worker.
+        for key in 0..32 {
+            assert_eq!(ask(&bus, key).await, owners[key as usize % 8]);
+        }
+
+        // growing the pool moves keys to the new worker only.
+        router.resize(5).await.unwrap();
+        for key in 0..8 {
+            let owner = ask(&bus, key).await;
+            assert!(owner == owners[key as usize] || owner == 4, "key {key}");
+        }
+
+        router.stop(Duration::from_secs(1)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn smallest_mailbox() {
+        let mut routing = Routing::<u32>::smallest_mailbox();
+
+        // the workers never run, their mailboxes only fill up.
+        let bus = Bus::<u8, u32>::spawn().await.unwrap();
+        let mut receivers = Vec::new();
+        let mut workers = Vec::new();
+        for (id, queued) in [(1, 2), (2, 1), (3, 1)] {
+            let (tx, rx) = mailbox::<u8, u32>(MailboxConfig::unbounded());
+            for _ in 0..queued {
+                tx.send(Message::new(1, 10, 0)).await.unwrap();
+            }
+            let (_actor, cell) = Actor::start(
+                10,
+                |_msg, _ctx: &mut Context<u8, u32>| {},
+                MailboxConfig::default(),
+                &bus,
+            );
+            receivers.push(rx);
+            workers.push(Worker { id, tx, cell });
+        }
+
+        assert_eq!(routing.select(&0, &workers), 1);
+        workers[1].tx.send(Message::new(1, 10, 0)).await.unwrap();
+        assert_eq!(routing.select(&0, &workers), 2);
+    }
+
+    /// A router whose workers never run, their mailboxes only fill up.
+    struct IdleRouter {
+        router: Router<u8>,
+        inbound_tx: MailboxSender<u8, u32>,
+        txs: Vec<MailboxSender<u8, u32>>,
+        receivers: Vec<MailboxReceiver<u8, u32>>,
+    }
+
+    impl IdleRouter {
+        fn spawn(bus: &Bus<u8, u32>, config: MailboxConfig, size: u64) -> Self {
+            let mut txs = Vec::new();
+            let mut receivers = Vec::new();
+            let mut workers = Vec::new();
+            for id in 1..=size {
+                let (tx, rx) = mailbox::<u8, u32>(config);
+                let (_actor, cell) = Actor::start(
+                    10,
+                    |_msg, _ctx: &mut Context<u8, u32>| {},
+                    MailboxConfig::default(),
+                    bus,
+                );
+                txs.push(tx.clone());
+                receivers.push(rx);
+                workers.push(Worker { id, tx, cell });
+            }
+            let pool = Pool {
+                aid: 10,
+                routing: Routing::round_robin(),
+                mailbox: config,
+                resizer: None,
+                start: Box::new(|aid, config, bus: &Bus<u8, u32>| {
+                    Actor::start(aid, |_msg, _ctx: &mut Context<u8, u32>| {}, config, bus)
+                }),
+                workers,
+                next_id: size,
+                routed: 0,
+                bus: bus.clone(),
+            };
+
+            let (ctrl_tx, ctrl_rx) = channel::<RouterCtrl<u8>>(10);
+            let (inbound_tx, inbound_rx) = mailbox::<u8, u32>(MailboxConfig::default());
+            let (_handle, exit_rx) =
+                spawn_watched(Router::internal_loop(pool, inbound_rx, ctrl_rx));
+            let router = Router {
+                aid: 10,
+                ctrl_tx,
+                exit_rx,
+            };
+            Self {
+                router,
+                inbound_tx,
+                txs,
+                receivers,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn route_around_full_worker() {
+        let bus = Bus::<u8, u32>::spawn().await.unwrap();
+        let IdleRouter {
+            router,
+            inbound_tx,
+            txs,
+            mut receivers,
+        } = IdleRouter::spawn(&bus, MailboxConfig::bounded(1), 2);
+
+        // the first worker is full: the first message routed goes to the
+        // second worker instead, then both are full and the next message
+        // waits for room in the second worker, whose turn it is.
+        txs[0].send(Message::new(1, 10, 0)).await.unwrap();
+        for payload in 1..3 {
+            inbound_tx.send(Message::new(1, 10, payload)).await.unwrap();
+        }
+        tokio::task::yield_now().await;
+        assert_eq!(txs[1].len(), 1);
+
+        // a full worker does not hold up the control messages.
+        let size = tokio::time::timeout(Duration::from_secs(1), router.size()).await;
+        assert_eq!(size.unwrap().unwrap(), 2);
+
+        assert_eq!(receivers[1].recv().await.unwrap().into_payload(), 1);
+        assert_eq!(receivers[1].recv().await.unwrap().into_payload(), 2);
+        assert_eq!(receivers[0].recv().await.unwrap().into_payload(), 0);
+
+        router.stop(Duration::from_secs(1)).await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ask_full_pool() {
+        let mut bus = Bus::<u8, u32>::spawn().await.unwrap();
+        let mut dead_rx = bus.subscribe_dead_letters();
+        let config = MailboxConfig::bounded(1).with_overflow(OverflowPolicy::FailFast);
+        let idle = IdleRouter::spawn(&bus, config, 2);
+        bus.add_actor(10, idle.inbound_tx.clone()).await.unwrap();
+        for tx in &idle.txs {
+            tx.send(Message::new(1, 10, 0)).await.unwrap();
+        }
+
+        // the ask fails right away, long before its timeout.
+        let start = Instant::now();
+        let err = bus
+            .ask(Message::new(1, 10, 1), Duration::from_secs(10))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("mailbox full"), "{err}");
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        let event = dead_rx.recv().await.unwrap();
+        assert_eq!(
+            (event.tid, event.reason),
+            (10, DeadLetterReason::MailboxFull)
+        );
+        let dead_letters = bus.dead_letters().await.unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].msg.payload(), &1);
+
+        idle.router.stop(Duration::from_secs(1)).await.unwrap();
+    }
+
+    /// A worker which stops when it receives zero, and tells once it did.
+    struct Stopping(UnboundedSender<()>);
+
+    impl Handler<u8, u32> for Stopping {
+        fn handle(&mut self, msg: Message<u8, u32>, ctx: &mut Context<u8, u32>) {
+            if *msg.payload() == 0 {
+                ctx.stop();
+            } else {
+                ctx.reply(&msg, *msg.payload());
+            }
+        }
+
+        fn stopped(&mut self) {
+            let _ = self.0.send(());
+        }
+    }
+
+    #[tokio::test]
+    async fn replace_stopped_worker() {
+        let bus = Bus::<u8, u32>::spawn().await.unwrap();
+        let (stopped_tx, mut stopped_rx) = unbounded_channel();
+        let spec = RouterSpec::new(Routing::round_robin(), move || Stopping(stopped_tx.clone()));
+        let router = Router::spawn(10, spec, bus.clone()).await.unwrap();
+
+        bus.bus_tx().send(Message::new(1, 10, 0)).await.unwrap();
+        // the messages queued behind the one which stopped the worker are
+        // lost with its mailbox, which is gone once the worker stopped.
+        stopped_rx.recv().await.unwrap();
+        for payload in 1..4 {
+            assert_eq!(ask(&bus, payload).await, payload);
+        }
+        assert_eq!(router.size().await.unwrap(), 1);
+
+        router.stop(Duration::from_secs(1)).await.unwrap();
+    }
+
+    #[test]
+    fn resizer() {
+        let resizer = Resizer::new(2, 4).with_pressure_threshold(3);
+        assert_eq!(resizer.clamp(1), 2);
+        assert_eq!(resizer.clamp(9), 4);
+
+        // all busy: grow, up to the upper bound.
+        assert_eq!(resizer.target([3, 5, 4]), 4);
+        assert_eq!(resizer.target([3, 5, 4, 3]), 4);
+        // less than half busy: shrink, down to the lower bound.
+        assert_eq!(resizer.target([0, 1, 2, 3]), 3);
+        assert_eq!(resizer.target([0, 0]), 2);
+        // half busy: keep.
+        assert_eq!(resizer.target([0, 0, 3, 3]), 4);
+    }
+
+    #[tokio::test]
+    async fn resize_with_load() {
+        let bus = Bus::<u8, u32>::spawn().await.unwrap();
+        let spec = RouterSpec::new(Routing::round_robin(), numbered())
+            .with_workers(1)
+            .with_resizer(Resizer::new(1, 3).with_interval(1));
+        let router = Router::spawn(10, spec, bus.clone()).await.unwrap();
+
+        // one by one, the single worker is never busy.
+        for payload in 0..4 {
+            ask(&bus, payload).await;
+        }
+        assert_eq!(router.size().await.unwrap(), 1);
+
+        router.resize(3).await.unwrap();
+        assert_eq!(router.size().await.unwrap(), 3);
+        router.resize(8).await.unwrap();
+        assert_eq!(router.size().await.unwrap(), 3);
+
+        // idle workers are removed.
+        ask(&bus, 0).await;
+        assert_eq!(router.size().await.unwrap(), 2);
+
+        router.stop(Duration::from_secs(1)).await.unwrap();
+    }
+}
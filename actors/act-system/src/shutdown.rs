@@ -0,0 +1,63 @@
+use crate::{ActorCell, WorkerId};
+use std::fmt::Debug;
+use tokio::time::Instant;
+
+/// The outcome of stopping a group of actors.
+#[derive(Debug)]
+pub struct ShutdownReport<A> {
+    /// The actors which stopped before the deadline.
+    pub stopped: Vec<A>,
+    /// The actors which were still running at the deadline and were aborted.
+    pub timed_out: Vec<A>,
+    /// The workers of the routers which stopped before the deadline. They
+    /// run with the router id, so they are told apart by their number.
+    pub workers_stopped: Vec<WorkerId<A>>,
+    /// The workers of the routers which were aborted at the deadline.
+    pub workers_timed_out: Vec<WorkerId<A>>,
+}
+
+impl<A> Default for ShutdownReport<A> {
+    fn default() -> Self {
+        Self {
+            stopped: Vec::new(),
+            timed_out: Vec::new(),
+            workers_stopped: Vec::new(),
+            workers_timed_out: Vec::new(),
+        }
+    }
+}
+
+impl<A> ShutdownReport<A> {
+    /// Returns `true` if all the actors and workers stopped before the deadline.
+    pub fn is_clean(&self) -> bool {
+        self.timed_out.is_empty() && self.workers_timed_out.is_empty()
+    }
+
+    pub fn merge(&mut self, other: ShutdownReport<A>) {
+        self.stopped.extend(other.stopped);
+        self.timed_out.extend(other.timed_out);
+        self.workers_stopped.extend(other.workers_stopped);
+        self.workers_timed_out.extend(other.workers_timed_out);
+    }
+}
+
+/// Asks all the actors to stop and waits for them until the deadline.
+/// The actors are signalled in reverse order of their start.
+pub(crate) async fn stop_actors<A>(cells: Vec<ActorCell<A>>, deadline: Instant) -> ShutdownReport<A>
+where
+    A: Debug,
+{
+    for cell in cells.iter().rev() {
+        cell.signal_stop();
+    }
+
+    let mut report = ShutdownReport::default();
+    for mut cell in cells.into_iter().rev() {
+        if cell.join(deadline).await {
+            report.stopped.push(cell.aid);
+        } else {
+            report.timed_out.push(cell.aid);
+        }
+    }
+    report
+}
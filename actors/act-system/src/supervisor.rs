@@ -0,0 +1,602 @@
+use crate::{
+    registry::Registry, Actor, ActorCell, ActorPath, Bus, Handler, MailboxConfig, ShutdownReport,
+};
+use act_identifiers::ActorId;
+use anyhow::{anyhow, Result};
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt::Debug,
+    hash::Hash,
+    time::Duration,
+};
+use tokio::{
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        oneshot, watch,
+    },
+    task::AbortHandle,
+    time::Instant,
+};
+
+/// Describes which children are restarted when one of them panics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartStrategy {
+    /// Only the child which panicked is restarted.
+    OneForOne,
+    /// All the children are restarted.
+    OneForAll,
+    /// The child which panicked and all the children started after it are restarted.
+    RestForOne,
+}
+
+/// Starts a new instance of a child actor, registered with the given bus.
+type StartFn<A, P> =
+    Box<dyn Fn(MailboxConfig, &Bus<A, P>) -> (Actor<A, P>, ActorCell<A>) + Send + Sync>;
+
+/// The ChildSpec describes how a supervisor creates one of its children.
+/// The factory is called every time the child is (re)started, so each
+/// incarnation of the child starts with a fresh handler.
+pub struct ChildSpec<A, P>
+where
+    A: ActorId + Debug,
+{
+    aid: A,
+    path: Option<ActorPath>,
+    mailbox: MailboxConfig,
+    start: StartFn<A, P>,
+}
+
+impl<A, P> ChildSpec<A, P>
+where
+    A: ActorId + Clone + Debug + Send + Sync + 'static,
+    P: Send + 'static,
+{
+    pub fn new<F, H>(aid: A, factory: F) -> Self
+    where
+        F: Fn() -> H + Send + Sync + 'static,
+        H: Handler<A, P>,
+    {
+        let child_aid = aid.clone();
+        let start = Box::new(move |config: MailboxConfig, bus: &Bus<A, P>| {
+            Actor::start(child_aid.clone(), factory(), config, bus)
+        });
+        Self {
+            aid,
+            path: None,
+            mailbox: MailboxConfig::default(),
+            start,
+        }
+    }
+
+    /// Registers every incarnation of the child at the path, so it can be
+    /// found with [`System::lookup`](crate::System::lookup) across restarts.
+    pub fn with_path(mut self, path: ActorPath) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    /// Every incarnation of the child gets a mailbox built from the configuration.
+    pub fn with_mailbox(mut self, config: MailboxConfig) -> Self {
+        self.mailbox = config;
+        self
+    }
+
+    pub fn aid(&self) -> &A {
+        &self.aid
+    }
+
+    pub fn path(&self) -> Option<&ActorPath> {
+        self.path.as_ref()
+    }
+}
+
+/// The SupervisorSpec describes the children of a supervisor and how it
+/// reacts when they panic. If more than `max_restarts` restarts are needed
+/// within the `within` window, the supervisor gives up: it stops all its
+/// children and reports itself as failed.
+pub struct SupervisorSpec<A, P>
+where
+    A: ActorId + Debug,
+{
+    strategy: RestartStrategy,
+    max_restarts: usize,
+    within: Duration,
+    children: Vec<ChildSpec<A, P>>,
+}
+
+impl<A, P> SupervisorSpec<A, P>
+where
+    A: ActorId + Debug,
+{
+    pub fn new(strategy: RestartStrategy) -> Self {
+        Self {
+            strategy,
+            max_restarts: 3,
+            within: Duration::from_secs(5),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_intensity(mut self, max_restarts: usize, within: Duration) -> Self {
+        self.max_restarts = max_restarts;
+        self.within = within;
+        self
+    }
+
+    /// Adds a child. The children are started in the order they were added.
+    pub fn with_child(mut self, child: ChildSpec<A, P>) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub fn strategy(&self) -> RestartStrategy {
+        self.strategy
+    }
+}
+
+/// The status a supervisor publishes every time something changes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SupervisorStatus {
+    /// The number of children restarts performed so far.
+    pub restarts: usize,
+    /// The supervisor exceeded its restart intensity and gave up.
+    pub failed: bool,
+}
+
+/// The control messages the supervisor task accepts.
+enum SupervisorCtrl<A> {
+    Stop(Instant, oneshot::Sender<ShutdownReport<A>>),
+}
+
+/// The handle of a running supervisor.
+pub struct Supervisor<A> {
+    ctrl_tx: Sender<SupervisorCtrl<A>>,
+    status_rx: watch::Receiver<SupervisorStatus>,
+}
+
+impl<A> Clone for Supervisor<A> {
+    fn clone(&self) -> Self {
+        Self {
+            ctrl_tx: self.ctrl_tx.clone(),
+            status_rx: self.status_rx.clone(),
+        }
+    }
+}
+
+impl<A> Supervisor<A> {
+    pub fn status(&self) -> SupervisorStatus {
+        *self.status_rx.borrow()
+    }
+
+    /// Returns a receiver which is notified every time the status changes.
+    pub fn subscribe(&self) -> watch::Receiver<SupervisorStatus> {
+        self.status_rx.clone()
+    }
+
+    /// Stops the children, in reverse order of their start, and then the
+    /// supervisor. The children drain their inbound channels and run their
+    /// stop hooks; the ones still running after the timeout are aborted.
+    /// Fails if the supervisor already stopped or gave up.
+    pub async fn stop(&self, timeout: Duration) -> Result<ShutdownReport<A>> {
+        let (report_tx, report_rx) = oneshot::channel::<ShutdownReport<A>>();
+        let deadline = Instant::now() + timeout;
+        self.ctrl_tx
+            .send(SupervisorCtrl::Stop(deadline, report_tx))
+            .await
+            .map_err(|_e| anyhow!("The supervisor is not running"))?;
+        report_rx
+            .await
+            .map_err(|e| anyhow!("Failed to get the shutdown report [{e}]"))
+    }
+}
+
+/// The state the supervisor task keeps for each of its children.
+struct Child<A, P>
+where
+    A: ActorId + Debug,
+{
+    spec: ChildSpec<A, P>,
+    /// Incremented on every start, so events of old incarnations are ignored.
+    generation: usize,
+    stop_tx: Option<watch::Sender<bool>>,
+    abort: Option<AbortHandle>,
+}
+
+/// Everything the supervisor task owns once the children are started.
+struct SupervisorState<A, P>
+where
+    A: ActorId + Debug,
+{
+    strategy: RestartStrategy,
+    max_restarts: usize,
+    within: Duration,
+    children: Vec<Child<A, P>>,
+    bus: Bus<A, P>,
+    registry: Registry<A>,
+    exit_tx: Sender<ChildExit>,
+}
+
+/// Sent by the monitor of a child when the child task ends.
+struct ChildExit {
+    index: usize,
+    generation: usize,
+    panicked: bool,
+}
+
+impl<A> Supervisor<A>
+where
+    A: ActorId + Clone + Debug + Hash + Send + Sync + 'static,
+{
+    /// Starts the children, in order, and returns once all of them are
+    /// registered with the bus and the registry. Fails if the path of a
+    /// child is taken, after stopping the children already started.
+    pub(crate) async fn spawn<P>(
+        spec: SupervisorSpec<A, P>,
+        mut bus: Bus<A, P>,
+        registry: Registry<A>,
+    ) -> Result<Self>
+    where
+        P: Send + 'static,
+    {
+        let mut paths = HashSet::new();
+        for path in spec.children.iter().filter_map(ChildSpec::path) {
+            if !paths.insert(path) {
+                return Err(anyhow!("The path {path} is given to several children"));
+            }
+        }
+
+        let (ctrl_tx, ctrl_rx) = channel::<SupervisorCtrl<A>>(10);
+        let (status_tx, status_rx) = watch::channel(SupervisorStatus::default());
+        let (exit_tx, exit_rx) = channel::<ChildExit>(10);
+
+        let SupervisorSpec {
+            strategy,
+            max_restarts,
+            within,
+            children,
+        } = spec;
+
+        let mut children = children
+            .into_iter()
+            .map(|spec| Child {
+                spec,
+                generation: 0,
+                stop_tx: None,
+                abort: None,
+            })
+            .collect::<Vec<_>>();
+
+        for index in 0..children.len() {
+            if let Err(e) =
+                Self::start_child(&mut children, index, &mut bus, &registry, &exit_tx).await
+            {
+                for child in children[..=index].iter_mut().rev() {
+                    if let Some(stop_tx) = child.stop_tx.take() {
+                        let _ = stop_tx.send(true);
+                    }
+                    let _ = bus.stopped(child.spec.aid.clone()).await;
+                }
+                return Err(e);
+            }
+        }
+
+        let state = SupervisorState {
+            strategy,
+            max_restarts,
+            within,
+            children,
+            bus,
+            registry,
+            exit_tx,
+        };
+        tokio::spawn(Self::internal_loop(state, exit_rx, ctrl_rx, status_tx));
+
+        Ok(Self { ctrl_tx, status_rx })
+    }
+
+    async fn internal_loop<P>(
+        state: SupervisorState<A, P>,
+        mut exit_rx: Receiver<ChildExit>,
+        mut ctrl_rx: Receiver<SupervisorCtrl<A>>,
+        status_tx: watch::Sender<SupervisorStatus>,
+    ) where
+        P: Send + 'static,
+    {
+        let SupervisorState {
+            strategy,
+            max_restarts,
+            within,
+            mut children,
+            mut bus,
+            registry,
+            exit_tx,
+        } = state;
+
+        let mut restarts = VecDeque::<Instant>::new();
+
+        loop {
+            tokio::select! {
+                msg = ctrl_rx.recv() => match msg {
+                    Some(SupervisorCtrl::Stop(deadline, report_tx)) => {
+                        let report = Self::stop_children(&mut children, &mut exit_rx, deadline).await;
+                        let _ = report_tx.send(report);
+                        break;
+                    }
+                    None => break, // all the handles have dropped
+                },
+                Some(exit) = exit_rx.recv() => {
+                    let child = &mut children[exit.index];
+                    if child.generation != exit.generation {
+                        // An incarnation we aborted ourselves.
+                        continue;
+                    }
+                    child.stop_tx = None;
+                    child.abort = None;
+                    if !exit.panicked {
+                        // The child stopped on its own, nothing to restart.
+                        let _ = bus.stopped(child.spec.aid.clone()).await;
+                        continue;
+                    }
+
+                    let now = Instant::now();
+                    while restarts.front().is_some_and(|t| now.duration_since(*t) > within) {
+                        restarts.pop_front();
+                    }
+                    if restarts.len() >= max_restarts {
+                        status_tx.send_modify(|status| status.failed = true);
+                        break;
+                    }
+                    restarts.push_back(now);
+
+                    let range = match strategy {
+                        RestartStrategy::OneForOne => exit.index..exit.index + 1,
+                        RestartStrategy::OneForAll => 0..children.len(),
+                        RestartStrategy::RestForOne => exit.index..children.len(),
+                    };
+                    for index in range.clone().rev() {
+                        Self::abort_child(&mut children[index]);
+                        let aid = children[index].spec.aid.clone();
+                        let _ = bus.restarting(aid).await;
+                    }
+                    for index in range {
+                        // A child whose path was taken meanwhile runs without it.
+                        let _ =
+                            Self::start_child(&mut children, index, &mut bus, &registry, &exit_tx)
+                                .await;
+                    }
+
+                    status_tx.send_modify(|status| status.restarts += 1);
+                }
+            }
+        }
+
+        for child in children.iter_mut().rev() {
+            Self::abort_child(child);
+        }
+    }
+
+    /// Signals all the running children to stop and waits for them until
+    /// the deadline, aborting the ones which are late.
+    async fn stop_children<P>(
+        children: &mut [Child<A, P>],
+        exit_rx: &mut Receiver<ChildExit>,
+        deadline: Instant,
+    ) -> ShutdownReport<A> {
+        for child in children.iter().rev() {
+            if let Some(stop_tx) = child.stop_tx.as_ref() {
+                let _ = stop_tx.send(true);
+            }
+        }
+
+        while children.iter().any(|child| child.abort.is_some()) {
+            let Ok(Some(exit)) = tokio::time::timeout_at(deadline, exit_rx.recv()).await else {
+                break;
+            };
+            let child = &mut children[exit.index];
+            if child.generation == exit.generation {
+                child.stop_tx = None;
+                child.abort = None;
+            }
+        }
+
+        let mut report = ShutdownReport::default();
+        for child in children.iter_mut().rev() {
+            let aid = child.spec.aid.clone();
+            if child.abort.is_some() {
+                Self::abort_child(child);
+                report.timed_out.push(aid);
+            } else {
+                report.stopped.push(aid);
+            }
+        }
+        report
+    }
+
+    /// Starts the child at the index. Fails if its path is taken, in which
+    /// case the child is started and registered without a path anyway.
+    async fn start_child<P>(
+        children: &mut [Child<A, P>],
+        index: usize,
+        bus: &mut Bus<A, P>,
+        registry: &Registry<A>,
+        exit_tx: &Sender<ChildExit>,
+    ) -> Result<()>
+    where
+        P: Send + 'static,
+    {
+        let child = &mut children[index];
+        child.generation += 1;
+
+        let (actor, cell) = (child.spec.start)(child.spec.mailbox, bus);
+        let ActorCell {
+            stop_tx,
+            handle,
+            exit_rx,
+            ..
+        } = cell;
+        child.stop_tx = Some(stop_tx);
+        child.abort = Some(handle.abort_handle());
+        // Every incarnation is registered again, at the same path. The bus
+        // is told about the children which stop for good by the supervisor.
+        let registered = registry
+            .register(
+                actor.aid().clone(),
+                child.spec.path.clone(),
+                exit_rx.clone(),
+                async {},
+            )
+            .inspect_err(|e| {
+                tracing::warn!(aid = ?actor.aid(), "Failed to register child at its path [{e}]");
+                let _ = registry.register(actor.aid().clone(), None, exit_rx, async {});
+            });
+        let _ = bus.add_actor(actor.aid().clone(), actor.tx()).await;
+
+        // Monitor the child task and report back when it ends.
+        let exit_tx = exit_tx.clone();
+        let generation = child.generation;
+        tokio::spawn(async move {
+            let panicked = matches!(handle.await, Err(e) if e.is_panic());
+            let _ = exit_tx
+                .send(ChildExit {
+                    index,
+                    generation,
+                    panicked,
+                })
+                .await;
+        });
+        registered
+    }
+
+    fn abort_child<P>(child: &mut Child<A, P>) {
+        child.stop_tx = None;
+        if let Some(abort) = child.abort.take() {
+            abort.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Context, Message};
+
+    /// Accumulates the payloads and panics when it receives zero.
+    #[derive(Default)]
+    struct Summer(u32);
+
+    impl Handler<u8, u32> for Summer {
+        fn handle(&mut self, msg: Message<u8, u32>, ctx: &mut Context<u8, u32>) {
+            if *msg.payload() == 0 {
+                panic!("zero is not allowed");
+            }
+            self.0 += msg.payload();
+            ctx.reply(&msg, self.0);
+        }
+    }
+
+    async fn sum(bus: &Bus<u8, u32>, aid: u8, value: u32) -> Result<u32> {
+        let reply = bus
+            .ask(Message::new(0, aid, value), Duration::from_secs(1))
+            .await?;
+        Ok(reply.into_payload())
+    }
+
+    async fn crash(bus: &Bus<u8, u32>, aid: u8, supervisor: &Supervisor<u8>, restarts: usize) {
+        let mut status_rx = supervisor.subscribe();
+        bus.bus_tx().send(Message::new(0, aid, 0)).await.unwrap();
+        status_rx
+            .wait_for(|status| status.restarts == restarts || status.failed)
+            .await
+            .unwrap();
+    }
+
+    fn spec(strategy: RestartStrategy) -> SupervisorSpec<u8, u32> {
+        SupervisorSpec::new(strategy)
+            .with_child(ChildSpec::new(1, Summer::default))
+            .with_child(ChildSpec::new(2, Summer::default))
+            .with_child(ChildSpec::new(3, Summer::default))
+    }
+
+    async fn spawn(strategy: RestartStrategy) -> (Bus<u8, u32>, Supervisor<u8>) {
+        let bus = Bus::<u8, u32>::spawn().await.unwrap();
+        let supervisor = Supervisor::spawn(spec(strategy), bus.clone(), Registry::new())
+            .await
+            .unwrap();
+        for aid in 1..=3 {
+            assert_eq!(sum(&bus, aid, 10).await.unwrap(), 10);
+        }
+        (bus, supervisor)
+    }
+
+    #[tokio::test]
+    async fn one_for_one() {
+        let (bus, supervisor) = spawn(RestartStrategy::OneForOne).await;
+
+        crash(&bus, 2, &supervisor, 1).await;
+        assert_eq!(supervisor.status().restarts, 1);
+
+        assert_eq!(sum(&bus, 1, 1).await.unwrap(), 11);
+        assert_eq!(sum(&bus, 2, 1).await.unwrap(), 1);
+        assert_eq!(sum(&bus, 3, 1).await.unwrap(), 11);
+
+        let report = supervisor.stop(Duration::from_secs(1)).await.unwrap();
+        assert!(report.is_clean());
+        assert_eq!(report.stopped, vec![3, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn one_for_all() {
+        let (bus, supervisor) = spawn(RestartStrategy::OneForAll).await;
+
+        crash(&bus, 2, &supervisor, 1).await;
+
+        assert_eq!(sum(&bus, 1, 1).await.unwrap(), 1);
+        assert_eq!(sum(&bus, 2, 1).await.unwrap(), 1);
+        assert_eq!(sum(&bus, 3, 1).await.unwrap(), 1);
+
+        let report = supervisor.stop(Duration::from_secs(1)).await.unwrap();
+        assert!(report.is_clean());
+        assert_eq!(report.stopped, vec![3, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn rest_for_one() {
+        let (bus, supervisor) = spawn(RestartStrategy::RestForOne).await;
+
+        crash(&bus, 2, &supervisor, 1).await;
+
+        assert_eq!(sum(&bus, 1, 1).await.unwrap(), 11);
+        assert_eq!(sum(&bus, 2, 1).await.unwrap(), 1);
+        assert_eq!(sum(&bus, 3, 1).await.unwrap(), 1);
+
+        let report = supervisor.stop(Duration::from_secs(1)).await.unwrap();
+        assert!(report.is_clean());
+        assert_eq!(report.stopped, vec![3, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn exceed_intensity() {
+        let bus = Bus::<u8, u32>::spawn().await.unwrap();
+        let spec = SupervisorSpec::new(RestartStrategy::OneForOne)
+            .with_intensity(2, Duration::from_secs(60))
+            .with_child(ChildSpec::new(1, Summer::default));
+        let supervisor = Supervisor::spawn(spec, bus.clone(), Registry::new())
+            .await
+            .unwrap();
+
+        crash(&bus, 1, &supervisor, 1).await;
+        crash(&bus, 1, &supervisor, 2).await;
+        assert!(!supervisor.status().failed);
+
+        crash(&bus, 1, &supervisor, 3).await;
+        let status = supervisor.status();
+        assert!(status.failed);
+        assert_eq!(status.restarts, 2);
+
+        // the child is not restarted anymore.
+        assert!(sum(&bus, 1, 1).await.is_err());
+
+        // the supervisor is not running anymore.
+        assert!(supervisor.stop(Duration::from_secs(1)).await.is_err());
+    }
+}
@@ -1,74 +1,886 @@
-use crate::{Actor, Bus};
-use act_identifiers::ActorId;
-use anyhow::{Ok, Result};
-use std::fmt::Debug;
+use crate::{
+    registry::Registry, shutdown::stop_actors, Actor, ActorCell, ActorPath, Bus, DeadLetter,
+    DeadLetterEvent, DeathWatch, ExitReason, Handler, HealthResponse, MailboxConfig, Message,
+    Metrics, Node, NoopMetrics, Peer, Router, RouterSpec, ShutdownReport, Supervisor,
+    SupervisorSpec, Terminated, TimerHandle, Wire, DEFAULT_BUS_CAPACITY,
+};
+use act_identifiers::{ActorId, Binary, FromId};
+use anyhow::{anyhow, Ok, Result};
+use std::{fmt::Debug, hash::Hash, sync::Arc, time::Duration};
+use tokio::{
+    sync::{broadcast, watch},
+    time::Instant,
+};
 
-pub struct System<A>
+/// How long [`System::stop`] waits for the actors to stop.
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct System<A, P>
 where
     A: ActorId + Debug,
 {
-    bus: Bus<A>,
+    bus: Bus<A, P>,
+    actors: Vec<ActorCell<A>>,
+    supervisors: Vec<Supervisor<A>>,
+    routers: Vec<Router<A>>,
+    registry: Registry<A>,
+    stopping: bool,
 }
 
-impl<A> System<A>
+impl<A, P> System<A, P>
 where
-    A: ActorId + Copy + Debug + Send + 'static,
+    A: ActorId + Copy + Debug + Hash + Send + 'static,
+    P: Send + 'static,
 {
     pub async fn spawn() -> Result<Self> {
+        Self::spawn_with_metrics(Arc::new(NoopMetrics)).await
+    }
+
+    /// Spawns the system reporting the message flow to the given metrics.
+    pub async fn spawn_with_metrics(metrics: Arc<dyn Metrics<A>>) -> Result<Self> {
         // create the dispatcher
-        let mut bus = Bus::<A>::spawn().await.unwrap();
+        let mut bus = Bus::<A, P>::spawn_with_metrics(DEFAULT_BUS_CAPACITY, metrics).await?;
 
         // check the health of the dispatcher
-        let health = bus.query_health().await.unwrap();
-        println!("SYSTEM: bus health is: {health:?}");
+        let health = bus.query_health().await?;
+        tracing::debug!(health = ?health.health, "bus spawned");
 
-        Ok(Self { bus })
+        Ok(Self {
+            bus,
+            actors: Vec::new(),
+            supervisors: Vec::new(),
+            routers: Vec::new(),
+            registry: Registry::new(),
+            stopping: false,
+        })
     }
 
     pub async fn stop(&mut self) -> Result<()> {
+        self.shutdown(STOP_TIMEOUT).await.map(|_| ())
+    }
+
+    /// Stops the system in order: it stops accepting new messages, it asks
+    /// the routers, the supervisors and the actors, in reverse order of their start, to
+    /// drain their inbound channels and to run their stop hooks, it waits for
+    /// all of them until the timeout and finally it stops the bus. The actors
+    /// still running after the timeout are aborted and listed in the report.
+    pub async fn shutdown(&mut self, timeout: Duration) -> Result<ShutdownReport<A>> {
+        if self.stopping {
+            return Err(anyhow!("The system is already stopped"));
+        }
+        self.stopping = true;
+
+        let deadline = Instant::now() + timeout;
+        let mut report = ShutdownReport::default();
+
+        for router in self.routers.drain(..).rev() {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match router.stop(timeout).await {
+                Result::Ok(workers) => report.merge(workers),
+                // The router task is gone, its workers cannot be accounted for.
+                Err(_) => report.timed_out.push(*router.aid()),
+            }
+        }
+
+        for supervisor in self.supervisors.drain(..).rev() {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            // A supervisor which gave up has no children left to stop.
+            if let Result::Ok(children) = supervisor.stop(timeout).await {
+                report.merge(children);
+            }
+        }
+
+        self.prune();
+        let actors = std::mem::take(&mut self.actors);
+        report.merge(stop_actors(actors, deadline).await);
+
         // Ask bus to stop.
-        self.bus.stop().await.unwrap();
+        self.bus.stop().await?;
+
+        Ok(report)
+    }
+
+    /// Reports the health of the actors registered with the bus, see [`Bus::query_health`].
+    pub async fn query_health(&mut self) -> Result<HealthResponse<A>> {
+        self.bus.query_health().await
+    }
+
+    /// The messages the bus could not deliver, see [`Bus::dead_letters`].
+    pub async fn dead_letters(&mut self) -> Result<Vec<DeadLetter<A, P>>>
+    where
+        P: Clone,
+    {
+        self.bus.dead_letters().await
+    }
+
+    /// Takes the messages the bus could not deliver, see [`Bus::drain_dead_letters`].
+    pub async fn drain_dead_letters(&mut self) -> Result<Vec<DeadLetter<A, P>>> {
+        self.bus.drain_dead_letters().await
+    }
+
+    /// Subscribes to the messages the bus could not deliver from now on.
+    pub fn subscribe_dead_letters(&self) -> broadcast::Receiver<DeadLetterEvent<A>> {
+        self.bus.subscribe_dead_letters()
+    }
+
+    /// Drops the cells of the actors which already terminated.
+    fn prune(&mut self) {
+        self.actors.retain(|cell| cell.exit_rx.borrow().is_none());
+    }
+
+    fn ensure_running(&self) -> Result<()> {
+        if self.stopping {
+            Err(anyhow!("The system is stopping"))
+        } else {
+            Ok(())
+        }
+    }
 
-        Ok(())
+    /// Spawns a new actor which is going to pass all the messages
+    /// it receives to the given handler.
+    pub async fn spawn_actor<H>(&mut self, aid: A, handler: H) -> Result<Actor<A, P>>
+    where
+        H: Handler<A, P>,
+    {
+        self.spawn_actor_with_mailbox(aid, handler, MailboxConfig::default())
+            .await
     }
 
-    pub async fn spawn_actor(&mut self, aid: A) -> Result<Actor<A>> {
-        let actors_tx = self.bus.bus_tx();
-        let actor = Actor::spawn(aid, actors_tx).await.unwrap();
+    /// Spawns a new actor with a mailbox built from the given configuration.
+    pub async fn spawn_actor_with_mailbox<H>(
+        &mut self,
+        aid: A,
+        handler: H,
+        config: MailboxConfig,
+    ) -> Result<Actor<A, P>>
+    where
+        H: Handler<A, P>,
+    {
+        self.start_actor(None, aid, handler, config).await
+    }
+
+    /// Spawns a new actor registered at the given path, like
+    /// `/user/orders/worker-3`, so it can be found with [`System::lookup`].
+    /// The path is released when the actor terminates. Fails if the path
+    /// is invalid or another running actor is registered at it.
+    pub async fn spawn_actor_at<H>(&mut self, path: &str, aid: A, handler: H) -> Result<Actor<A, P>>
+    where
+        H: Handler<A, P>,
+    {
+        let path = path.parse::<ActorPath>()?;
+        self.start_actor(Some(path), aid, handler, MailboxConfig::default())
+            .await
+    }
+
+    async fn start_actor<H>(
+        &mut self,
+        path: Option<ActorPath>,
+        aid: A,
+        handler: H,
+        config: MailboxConfig,
+    ) -> Result<Actor<A, P>>
+    where
+        H: Handler<A, P>,
+    {
+        self.ensure_running()?;
+        self.prune();
+        let (actor, cell) = Actor::start(aid, handler, config, &self.bus);
+        if let Err(e) = self.register(aid, path, cell.exit_rx.clone()) {
+            // Nothing can have been sent to the actor yet.
+            cell.signal_stop();
+            return Err(e);
+        }
+        self.actors.push(cell);
 
         // Add the actor to the bus, so the bus can start sending messages to it.
-        self.bus.add_actor(actor.aid, actor.tx());
+        self.bus.add_actor(actor.aid, actor.tx()).await?;
 
         Ok(actor)
     }
+
+    /// Registers with the registry an actor which the bus forgets once it
+    /// terminates. Fails if the path is taken.
+    fn register(
+        &self,
+        aid: A,
+        path: Option<ActorPath>,
+        exit_rx: watch::Receiver<Option<ExitReason>>,
+    ) -> Result<()> {
+        let bus = self.bus.clone();
+        let stopped = async move {
+            let _ = bus.stopped(aid).await;
+        };
+        self.registry.register(aid, path, exit_rx, stopped)
+    }
+
+    /// The id of the running actor registered at the path.
+    pub fn lookup(&self, path: &str) -> Option<A> {
+        let path = path.parse::<ActorPath>().ok()?;
+        self.registry.lookup(&path)
+    }
+
+    /// The path the running actor is registered at, if it has one.
+    pub fn path_of(&self, aid: &A) -> Option<ActorPath> {
+        self.registry.path_of(aid)
+    }
+
+    /// The running actors registered right below the path, ordered by path.
+    pub fn children(&self, path: &str) -> Result<Vec<(ActorPath, A)>> {
+        let path = path.parse::<ActorPath>()?;
+        Ok(self.registry.children(&path))
+    }
+
+    /// Watches for the termination of an actor spawned by the system.
+    /// Returns `None` if no such actor is running.
+    pub fn watch(&self, aid: &A) -> Option<DeathWatch<A>> {
+        self.registry.watch(aid)
+    }
+
+    /// Subscribes to the termination of the actors spawned by the system from now on.
+    pub fn subscribe_terminations(&self) -> broadcast::Receiver<Terminated<A>> {
+        self.registry.subscribe()
+    }
+
+    /// Spawns a supervisor, which starts the children described by the spec
+    /// and restarts them, according to the spec's strategy, when they panic.
+    /// The children given a path, see [`ChildSpec::with_path`], are
+    /// registered at it, again on every restart. Fails if one of the paths
+    /// is taken. The system keeps a handle of the supervisor, so it can
+    /// stop it on shutdown.
+    pub async fn spawn_supervisor(&mut self, spec: SupervisorSpec<A, P>) -> Result<Supervisor<A>>
+    where
+        A: Sync,
+    {
+        self.ensure_running()?;
+        let supervisor = Supervisor::spawn(spec, self.bus.clone(), self.registry.clone()).await?;
+        self.supervisors.push(supervisor.clone());
+        Ok(supervisor)
+    }
+
+    /// Spawns a router registered with the bus under the given id, which
+    /// passes each message sent to that id to one of the workers described
+    /// by the spec. The system keeps a handle of the router, so it can stop
+    /// it on shutdown.
+    pub async fn spawn_router(&mut self, aid: A, spec: RouterSpec<A, P>) -> Result<Router<A>> {
+        self.start_router(None, aid, spec).await
+    }
+
+    /// Spawns a router, see [`System::spawn_router`], registered at the
+    /// given path. Fails if the path is invalid or taken.
+    pub async fn spawn_router_at(
+        &mut self,
+        path: &str,
+        aid: A,
+        spec: RouterSpec<A, P>,
+    ) -> Result<Router<A>> {
+        let path = path.parse::<ActorPath>()?;
+        self.start_router(Some(path), aid, spec).await
+    }
+
+    async fn start_router(
+        &mut self,
+        path: Option<ActorPath>,
+        aid: A,
+        spec: RouterSpec<A, P>,
+    ) -> Result<Router<A>> {
+        self.ensure_running()?;
+        let router = Router::spawn(aid, spec, self.bus.clone()).await?;
+        // Dropping the only handle of the router stops it and its workers.
+        self.register(aid, path, router.exit_rx.clone())?;
+        self.routers.push(router.clone());
+        Ok(router)
+    }
+
+    /// Listens for peers on the given address, so actors living on other
+    /// nodes can send messages to the actors of this system, see [`Node::listen`].
+    pub async fn listen<S>(&self, addr: S) -> Result<Node>
+    where
+        S: tokio::net::ToSocketAddrs,
+        A: Binary,
+        P: Wire,
+    {
+        self.ensure_running()?;
+        Node::listen(addr, &self.bus).await
+    }
+
+    /// Registers an actor living on the node the peer is connected to, so
+    /// the actors of this system can send it messages, see [`Peer::register`].
+    pub async fn register_remote(&mut self, aid: A, peer: &Peer<A, P>) -> Result<()>
+    where
+        A: Binary,
+        P: Wire,
+    {
+        self.ensure_running()?;
+        let (_handle, exit_rx) = peer.register_watched(aid, &mut self.bus).await?;
+        self.register(aid, None, exit_rx)
+    }
+
+    /// Registers a remote actor, see [`System::register_remote`], at the
+    /// given path. The path is released when the connection is lost.
+    /// Fails if the path is invalid or taken.
+    pub async fn register_remote_at(&mut self, path: &str, aid: A, peer: &Peer<A, P>) -> Result<()>
+    where
+        A: Binary,
+        P: Wire,
+    {
+        self.ensure_running()?;
+        let path = path.parse::<ActorPath>()?;
+        let (handle, exit_rx) = peer.register_watched(aid, &mut self.bus).await?;
+        self.register(aid, Some(path), exit_rx).inspect_err(|_e| {
+            // Stop forwarding to the remote actor, which closes its mailbox.
+            handle.abort();
+        })
+    }
+
+    /// Sends a message through the bus, which routes it to the destination actor.
+    pub async fn send(&self, msg: Message<A, P>) -> Result<()> {
+        self.ensure_running()?;
+        self.bus
+            .bus_tx()
+            .send(msg)
+            .await
+            .map_err(|_e| anyhow!("Failed to send the message to the bus"))
+    }
+
+    /// Sends a copy of the payload to every actor subscribed to the topic,
+    /// see [`Bus::publish`].
+    pub async fn publish<F, T>(&self, f: F, topic: T, payload: P) -> Result<usize>
+    where
+        F: Into<FromId<A>>,
+        T: Into<String>,
+        P: Clone,
+    {
+        self.ensure_running()?;
+        self.bus.publish(f, topic, payload).await
+    }
+
+    /// Sends a copy of the payload to every registered actor, see [`Bus::broadcast`].
+    pub async fn broadcast<F>(&self, f: F, payload: P) -> Result<usize>
+    where
+        F: Into<FromId<A>>,
+        P: Clone,
+    {
+        self.ensure_running()?;
+        self.bus.broadcast(f, payload).await
+    }
+
+    /// Sends a message through the bus once the delay elapsed, see [`Bus::send_after`].
+    pub fn send_after(&self, msg: Message<A, P>, delay: Duration) -> Result<TimerHandle> {
+        self.ensure_running()?;
+        Ok(self.bus.send_after(msg, delay))
+    }
+
+    /// Sends a clone of the message through the bus every period, see [`Bus::send_interval`].
+    pub fn send_interval(&self, msg: Message<A, P>, period: Duration) -> Result<TimerHandle>
+    where
+        P: Clone,
+    {
+        self.ensure_running()?;
+        self.bus.send_interval(msg, period)
+    }
+
+    /// Sends a message through the bus and waits for the destination
+    /// actor to reply to it, see [`Bus::ask`].
+    pub async fn ask(&self, msg: Message<A, P>, timeout: Duration) -> Result<Message<A, P>> {
+        self.ensure_running()?;
+        self.bus.ask(msg, timeout).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
-    use std::time::Duration;
-    use tokio::time::sleep;
+    use crate::{
+        ActorStatus, ChildSpec, Context, DeadLetterReason, ExitReason, Health, RestartStrategy,
+        Routing,
+    };
+    use tokio::sync::mpsc::channel;
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn run_and_quit() {
-        const SLEEP: u64 = 1000;
-        eprintln!("SYSTEM: Run and Quit starting ...");
+        let mut system = System::<u8, ()>::spawn().await.unwrap();
 
-        let mut system = System::<u8>::spawn().await.unwrap();
+        let aid = 10_u8;
+        let actor = system
+            .spawn_actor(aid, |_msg, _ctx: &mut Context<u8, ()>| {})
+            .await
+            .unwrap();
+        assert_eq!(actor.aid(), &aid);
 
-        sleep(Duration::from_millis(SLEEP)).await;
+        system.stop().await.unwrap();
+    }
 
-        eprintln!("SYSTEM: Creating an actor ...");
+    #[tokio::test]
+    async fn deliver_payload() {
+        let mut system = System::<u8, String>::spawn().await.unwrap();
 
-        let aid = 10_u8;
-        let actor = system.spawn_actor(aid).await.unwrap();
-        assert_eq!(actor.aid(), &aid);
+        let (tx, mut rx) = channel::<(u8, String)>(10);
+        system
+            .spawn_actor(
+                10,
+                move |msg: Message<u8, String>, _ctx: &mut Context<u8, String>| {
+                    let fid = *msg.fid().as_ref();
+                    tx.try_send((fid, msg.into_payload())).unwrap();
+                },
+            )
+            .await
+            .unwrap();
 
-        sleep(Duration::from_millis(SLEEP)).await;
+        system
+            .send(Message::new(1, 10, "hello".to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(rx.recv().await.unwrap(), (1, "hello".to_string()));
 
         system.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn ping_pong() {
+        let mut system = System::<u8, String>::spawn().await.unwrap();
+
+        // the pong actor replies to every message it receives.
+        system
+            .spawn_actor(
+                10,
+                |msg: Message<u8, String>, ctx: &mut Context<u8, String>| {
+                    let payload = format!("pong {}", msg.payload());
+                    ctx.reply(&msg, payload);
+                },
+            )
+            .await
+            .unwrap();
+
+        // the ping actor sends the first message when it starts.
+        struct Ping(tokio::sync::mpsc::Sender<String>);
+        impl Handler<u8, String> for Ping {
+            fn started(&mut self, ctx: &mut Context<u8, String>) {
+                ctx.send(10, "ping".to_string());
+            }
+
+            fn handle(&mut self, msg: Message<u8, String>, _ctx: &mut Context<u8, String>) {
+                self.0.try_send(msg.into_payload()).unwrap();
+            }
+        }
+
+        let (tx, mut rx) = channel::<String>(10);
+        system.spawn_actor(20, Ping(tx)).await.unwrap();
+
+        assert_eq!(rx.recv().await.unwrap(), "pong ping");
+
+        system.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn ask() {
+        let mut system = System::<u8, u32>::spawn().await.unwrap();
+
+        system
+            .spawn_actor(10, |msg: Message<u8, u32>, ctx: &mut Context<u8, u32>| {
+                ctx.reply(&msg, msg.payload() * 2);
+            })
+            .await
+            .unwrap();
+
+        let reply = system
+            .ask(Message::new(1, 10, 21), Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(reply.fid().as_ref(), &10);
+        assert_eq!(reply.payload(), &42);
+
+        system.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn publish_subscribe() {
+        let mut system = System::<u8, String>::spawn().await.unwrap();
+
+        let (tx, mut rx) = channel::<(u8, String)>(10);
+        for aid in [10, 20] {
+            let tx = tx.clone();
+            let actor = system
+                .spawn_actor(
+                    aid,
+                    move |msg: Message<u8, String>, ctx: &mut Context<u8, String>| {
+                        tx.try_send((*ctx.aid(), msg.into_payload())).unwrap();
+                    },
+                )
+                .await
+                .unwrap();
+            actor.subscribe("news").await.unwrap();
+        }
+
+        let count = system
+            .publish(1, "news", "extra".to_string())
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+
+        let mut received = vec![rx.recv().await.unwrap(), rx.recv().await.unwrap()];
+        received.sort();
+        assert_eq!(
+            received,
+            vec![(10, "extra".to_string()), (20, "extra".to_string())]
+        );
+
+        system.stop().await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timers() {
+        let mut system = System::<u8, &str>::spawn().await.unwrap();
+
+        // the actor schedules a tick to itself when it starts.
+        struct Ticker(tokio::sync::mpsc::Sender<&'static str>);
+        impl Handler<u8, &'static str> for Ticker {
+            fn started(&mut self, ctx: &mut Context<u8, &'static str>) {
+                ctx.send_after(*ctx.aid(), "tick", Duration::from_secs(10));
+            }
+
+            fn handle(
+                &mut self,
+                msg: Message<u8, &'static str>,
+                _ctx: &mut Context<u8, &'static str>,
+            ) {
+                self.0.try_send(msg.into_payload()).unwrap();
+            }
+        }
+
+        let (tx, mut rx) = channel::<&str>(10);
+        system.spawn_actor(10, Ticker(tx)).await.unwrap();
+
+        let start = Instant::now();
+        let cancelled = system
+            .send_after(Message::new(1, 10, "cancelled"), Duration::from_secs(5))
+            .unwrap();
+        cancelled.cancel();
+        system
+            .send_after(Message::new(1, 10, "later"), Duration::from_secs(20))
+            .unwrap();
+
+        assert_eq!(rx.recv().await.unwrap(), "tick");
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+        assert_eq!(rx.recv().await.unwrap(), "later");
+        assert_eq!(start.elapsed(), Duration::from_secs(20));
+
+        system.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn supervise() {
+        let mut system = System::<u8, u32>::spawn().await.unwrap();
+
+        let spec =
+            SupervisorSpec::new(RestartStrategy::OneForOne).with_child(ChildSpec::new(10, || {
+                |msg: Message<u8, u32>, ctx: &mut Context<u8, u32>| {
+                    assert_ne!(*msg.payload(), 0, "zero is not allowed");
+                    ctx.reply(&msg, *msg.payload());
+                }
+            }));
+        let supervisor = system.spawn_supervisor(spec).await.unwrap();
+        let mut status_rx = supervisor.subscribe();
+
+        system.send(Message::new(1, 10, 0)).await.unwrap();
+        status_rx
+            .wait_for(|status| status.restarts == 1)
+            .await
+            .unwrap();
+
+        let reply = system
+            .ask(Message::new(1, 10, 5), Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(reply.payload(), &5);
+
+        let health = system.query_health().await.unwrap();
+        assert_eq!(health.health, Health::Healthy);
+        assert_eq!(health.actor(&10).unwrap().status, ActorStatus::Running);
+
+        let report = system.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(report.stopped, vec![10]);
+        assert!(supervisor.stop(Duration::from_secs(1)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn lookup_and_watch() {
+        let mut system = System::<u8, u32>::spawn().await.unwrap();
+        let mut terminated_rx = system.subscribe_terminations();
+
+        // a worker stops itself when it receives zero and panics on one.
+        let worker = |msg: Message<u8, u32>, ctx: &mut Context<u8, u32>| match msg.payload() {
+            0 => ctx.stop(),
+            1 => panic!("one is not allowed"),
+            _ => {}
+        };
+        system
+            .spawn_actor_at("/user/orders", 1, worker)
+            .await
+            .unwrap();
+        system
+            .spawn_actor_at("/user/orders/worker-1", 2, worker)
+            .await
+            .unwrap();
+        system
+            .spawn_actor_at("/user/orders/worker-2", 3, worker)
+            .await
+            .unwrap();
+        system.spawn_actor(4, worker).await.unwrap();
+
+        assert_eq!(system.lookup("/user/orders/worker-2"), Some(3));
+        assert_eq!(system.lookup("/user/orders/worker-3"), None);
+        assert_eq!(
+            system.path_of(&2).unwrap().to_string(),
+            "/user/orders/worker-1"
+        );
+        assert_eq!(system.path_of(&4), None);
+        let children = system.children("/user/orders").unwrap();
+        assert_eq!(
+            children.iter().map(|(_, aid)| *aid).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert!(system
+            .spawn_actor_at("/user/orders/worker-1", 5, worker)
+            .await
+            .is_err());
+
+        let watch = system.watch(&2).unwrap();
+        system.send(Message::new(9, 2, 0)).await.unwrap();
+        assert_eq!(watch.terminated().await, ExitReason::Stopped);
+
+        let watch = system.watch(&3).unwrap();
+        system.send(Message::new(9, 3, 1)).await.unwrap();
+        assert_eq!(watch.terminated().await, ExitReason::Failed);
+
+        let mut terminated = [
+            terminated_rx.recv().await.unwrap(),
+            terminated_rx.recv().await.unwrap(),
+        ];
+        terminated.sort_by_key(|t| t.aid);
+        assert_eq!(
+            terminated[0].path.as_ref().unwrap().name(),
+            Some("worker-1")
+        );
+        assert_eq!(terminated[0].reason, ExitReason::Stopped);
+        assert_eq!(terminated[1].reason, ExitReason::Failed);
+
+        // the paths are released.
+        assert_eq!(system.lookup("/user/orders/worker-1"), None);
+        assert!(system.children("/user/orders").unwrap().is_empty());
+        assert!(system.watch(&2).is_none());
+        system
+            .spawn_actor_at("/user/orders/worker-1", 5, worker)
+            .await
+            .unwrap();
+
+        system.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn forget_terminated_actors() {
+        let mut system = System::<u8, u32>::spawn().await.unwrap();
+        let mut terminated_rx = system.subscribe_terminations();
+
+        // the actor stops itself when it receives zero.
+        let worker = |msg: Message<u8, u32>, ctx: &mut Context<u8, u32>| {
+            if *msg.payload() == 0 {
+                ctx.stop();
+            }
+        };
+        let actor = system.spawn_actor(10, worker).await.unwrap();
+        actor.subscribe("news").await.unwrap();
+        system.spawn_actor(20, worker).await.unwrap();
+
+        system.send(Message::new(1, 10, 0)).await.unwrap();
+        assert_eq!(terminated_rx.recv().await.unwrap().aid, 10);
+
+        // the bus forgot the actor, its mailbox and its subscriptions.
+        let health = system.query_health().await.unwrap();
+        assert!(health.actor(&10).is_none());
+        assert_eq!(system.publish(1, "news", 1).await.unwrap(), 0);
+        system.send(Message::new(1, 10, 1)).await.unwrap();
+        let dead_letters = system.dead_letters().await.unwrap();
+        assert_eq!(dead_letters[0].reason, DeadLetterReason::UnknownActor);
+
+        // the id can be used again.
+        system.spawn_actor(10, worker).await.unwrap();
+        assert_eq!(system.actors.len(), 2);
+        let health = system.query_health().await.unwrap();
+        assert_eq!(health.actor(&10).unwrap().status, ActorStatus::Running);
+
+        let report = system.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(report.stopped, vec![10, 20]);
+    }
+
+    #[tokio::test]
+    async fn router() {
+        let mut system = System::<u8, u32>::spawn().await.unwrap();
+
+        let spec = RouterSpec::new(Routing::round_robin(), || {
+            |msg: Message<u8, u32>, ctx: &mut Context<u8, u32>| {
+                ctx.reply(&msg, msg.payload() + 1);
+            }
+        })
+        .with_workers(2);
+        let router = system.spawn_router(10, spec).await.unwrap();
+        assert_eq!(router.size().await.unwrap(), 2);
+
+        let reply = system
+            .ask(Message::new(1, 10, 1), Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(reply.payload(), &2);
+        assert_eq!(reply.fid().as_ref(), &10);
+
+        let report = system.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert!(report.stopped.is_empty());
+        let workers = report
+            .workers_stopped
+            .iter()
+            .map(|worker| (worker.router, worker.worker))
+            .collect::<Vec<_>>();
+        assert_eq!(workers, vec![(10, 2), (10, 1)]);
+        assert!(router.size().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn register_paths() {
+        let mut system = System::<u8, u32>::spawn().await.unwrap();
+        let mut terminated_rx = system.subscribe_terminations();
+
+        // the supervised child panics on zero.
+        let child = ChildSpec::new(10, || {
+            |msg: Message<u8, u32>, ctx: &mut Context<u8, u32>| {
+                assert_ne!(*msg.payload(), 0, "zero is not allowed");
+                ctx.reply(&msg, *msg.payload());
+            }
+        })
+        .with_path("/user/supervised".parse().unwrap());
+        let spec = SupervisorSpec::new(RestartStrategy::OneForOne).with_child(child);
+        let supervisor = system.spawn_supervisor(spec).await.unwrap();
+        assert_eq!(system.lookup("/user/supervised"), Some(10));
+
+        // the restarted child is registered again at the same path.
+        let mut status_rx = supervisor.subscribe();
+        system.send(Message::new(1, 10, 0)).await.unwrap();
+        status_rx
+            .wait_for(|status| status.restarts == 1)
+            .await
+            .unwrap();
+        assert_eq!(terminated_rx.recv().await.unwrap().aid, 10);
+        assert_eq!(system.lookup("/user/supervised"), Some(10));
+        assert_eq!(system.watch(&10).unwrap().aid(), &10);
+
+        // a path given to a child is taken.
+        let spec = SupervisorSpec::new(RestartStrategy::OneForOne).with_child(
+            ChildSpec::new(11, || |_msg, _ctx: &mut Context<u8, u32>| {})
+                .with_path("/user/supervised".parse().unwrap()),
+        );
+        assert!(system.spawn_supervisor(spec).await.is_err());
+        // the child started before the path was refused is stopped.
+        assert_eq!(terminated_rx.recv().await.unwrap().aid, 11);
+        assert_eq!(system.lookup("/user/supervised"), Some(10));
+
+        let spec = RouterSpec::new(Routing::round_robin(), || {
+            |msg: Message<u8, u32>, ctx: &mut Context<u8, u32>| ctx.reply(&msg, 1)
+        });
+        let router = system
+            .spawn_router_at("/user/router", 20, spec)
+            .await
+            .unwrap();
+        assert_eq!(system.lookup("/user/router"), Some(20));
+        let children = system.children("/user").unwrap();
+        assert_eq!(
+            children.iter().map(|(_, aid)| *aid).collect::<Vec<_>>(),
+            vec![20, 10]
+        );
+
+        // the path of a stopped router is released.
+        router.stop(Duration::from_secs(1)).await.unwrap();
+        let terminated = terminated_rx.recv().await.unwrap();
+        assert_eq!(terminated.aid, 20);
+        assert_eq!(terminated.path.unwrap().to_string(), "/user/router");
+        assert_eq!(system.lookup("/user/router"), None);
+
+        // an actor living on another node.
+        let bus = Bus::<u8, u32>::spawn().await.unwrap();
+        let node = Node::listen("127.0.0.1:0", &bus).await.unwrap();
+        let peer = Peer::connect(node.local_addr()).await.unwrap();
+        system
+            .register_remote_at("/remote/echo", 30, &peer)
+            .await
+            .unwrap();
+        assert_eq!(system.lookup("/remote/echo"), Some(30));
+        assert!(system
+            .register_remote_at("/remote/echo", 31, &peer)
+            .await
+            .is_err());
+
+        system.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn graceful_shutdown() {
+        let mut system = System::<u8, u32>::spawn().await.unwrap();
+
+        struct Drain(u32, tokio::sync::mpsc::Sender<u32>);
+        impl Handler<u8, u32> for Drain {
+            fn handle(&mut self, msg: Message<u8, u32>, _ctx: &mut Context<u8, u32>) {
+                self.0 += msg.payload();
+            }
+
+            fn stopped(&mut self) {
+                self.1.try_send(self.0).unwrap();
+            }
+        }
+
+        let (tx, mut rx) = channel::<u32>(10);
+        let actor = system.spawn_actor(10, Drain(0, tx)).await.unwrap();
+        for i in 1..=5 {
+            actor.tx().send(Message::new(1, 10, i)).await.unwrap();
+        }
+
+        let report = system.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert!(report.is_clean());
+        assert_eq!(report.stopped, vec![10]);
+
+        // all the messages in the inbound channel were handled.
+        assert_eq!(rx.recv().await.unwrap(), 15);
+
+        // the system does not accept new messages anymore.
+        assert!(system.send(Message::new(1, 10, 1)).await.is_err());
+        assert!(system.shutdown(Duration::from_secs(1)).await.is_err());
+    }
+
+    // The hooks are synchronous, the actor blocks its worker thread until
+    // the test opens the gate, so the runtime needs another thread.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn shutdown_timeout() {
+        let mut system = System::<u8, ()>::spawn().await.unwrap();
+
+        struct Slow(std::sync::mpsc::Receiver<()>);
+        impl Handler<u8, ()> for Slow {
+            fn handle(&mut self, _msg: Message<u8, ()>, _ctx: &mut Context<u8, ()>) {}
+
+            fn stopping(&mut self, _ctx: &mut Context<u8, ()>) {
+                // Blocks until the gate is dropped.
+                let _ = self.0.recv();
+            }
+        }
+
+        let (gate, gate_rx) = std::sync::mpsc::channel::<()>();
+        system.spawn_actor(10, Slow(gate_rx)).await.unwrap();
+        system
+            .spawn_actor(20, |_msg, _ctx: &mut Context<u8, ()>| {})
+            .await
+            .unwrap();
 
-        eprintln!("SYSTEM: Run and Quit ended!");
+        let report = system.shutdown(Duration::from_millis(100)).await.unwrap();
+        drop(gate);
+        assert!(!report.is_clean());
+        assert_eq!(report.stopped, vec![20]);
+        assert_eq!(report.timed_out, vec![10]);
     }
 }
@@ -0,0 +1,274 @@
+use crate::{
+    mailbox, shutdown::stop_actors, Actor, ActorCell, Bus, DeadLetter, Handler, MailboxConfig,
+    MailboxReceiver, Message, ShutdownReport,
+};
+use act_identifiers::{ActorId, ToId};
+use anyhow::{anyhow, Result};
+use std::{fmt::Debug, hash::Hash, time::Duration};
+use tokio::time::Instant;
+
+/// The TestKit runs actors on a bus of their own and lets a test observe
+/// them through probes. Run the tests on tokio's paused clock, with
+/// `#[tokio::test(start_paused = true)]`, so timeouts and timers fire
+/// as soon as every task is idle, without sleeping for real.
+pub struct TestKit<A, P>
+where
+    A: ActorId + Debug,
+{
+    bus: Bus<A, P>,
+    actors: Vec<ActorCell<A>>,
+}
+
+impl<A, P> TestKit<A, P>
+where
+    A: ActorId + Clone + Debug + Hash + Send + 'static,
+    P: Send + 'static,
+{
+    pub async fn spawn() -> Result<Self> {
+        Ok(Self {
+            bus: Bus::spawn().await?,
+            actors: Vec::new(),
+        })
+    }
+
+    pub fn bus(&self) -> &Bus<A, P> {
+        &self.bus
+    }
+
+    /// Spawns the actor under test and registers it with the bus.
+    pub async fn spawn_actor<H>(&mut self, aid: A, handler: H) -> Result<Actor<A, P>>
+    where
+        H: Handler<A, P>,
+    {
+        let (actor, cell) = Actor::start(aid.clone(), handler, MailboxConfig::default(), &self.bus);
+        self.actors.push(cell);
+        self.bus.add_actor(aid, actor.tx()).await?;
+        Ok(actor)
+    }
+
+    /// Registers a probe with the bus, which receives the messages sent to
+    /// the given actor id instead of an actor.
+    pub async fn probe(&mut self, aid: A) -> Result<Probe<A, P>> {
+        let (tx, rx) = mailbox::<A, P>(MailboxConfig::unbounded());
+        self.bus.add_actor(aid.clone(), tx).await?;
+        Ok(Probe {
+            aid,
+            rx,
+            bus: self.bus.clone(),
+            received: 0,
+        })
+    }
+
+    /// Injects the message into the bus, as if an actor sent it.
+    pub async fn inject(&self, msg: Message<A, P>) -> Result<()> {
+        self.bus
+            .bus_tx()
+            .send(msg)
+            .await
+            .map_err(|_e| anyhow!("Failed to inject the message into the bus"))
+    }
+
+    /// Moves the paused clock forward, firing the timers due meanwhile,
+    /// and lets the woken tasks run.
+    pub async fn advance(&self, duration: Duration) {
+        tokio::time::advance(duration).await;
+        tokio::task::yield_now().await;
+    }
+
+    /// The messages the bus could not deliver, see [`Bus::dead_letters`].
+    pub async fn dead_letters(&mut self) -> Result<Vec<DeadLetter<A, P>>>
+    where
+        P: Clone,
+    {
+        self.bus.dead_letters().await
+    }
+
+    /// Takes the messages the bus could not deliver, see [`Bus::drain_dead_letters`].
+    pub async fn drain_dead_letters(&mut self) -> Result<Vec<DeadLetter<A, P>>> {
+        self.bus.drain_dead_letters().await
+    }
+
+    /// Stops the actors, waiting for them to drain their mailboxes, and the bus.
+    pub async fn stop(mut self, timeout: Duration) -> Result<ShutdownReport<A>> {
+        let deadline = Instant::now() + timeout;
+        let report = stop_actors(std::mem::take(&mut self.actors), deadline).await;
+        self.bus.stop().await?;
+        Ok(report)
+    }
+}
+
+/// A Probe stands in for an actor: it records the messages routed to
+/// it, so the test can assert on them, and it can send messages back.
+/// The expectations panic when they are not met.
+pub struct Probe<A, P>
+where
+    A: ActorId + Debug,
+{
+    aid: A,
+    rx: MailboxReceiver<A, P>,
+    bus: Bus<A, P>,
+    received: usize,
+}
+
+impl<A, P> Probe<A, P>
+where
+    A: ActorId + Clone + Debug,
+    P: Debug,
+{
+    pub fn aid(&self) -> &A {
+        &self.aid
+    }
+
+    /// The number of messages the probe received so far.
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    /// Waits for the next message, panics if none arrives before the timeout.
+    pub async fn expect_msg(&mut self, timeout: Duration) -> Message<A, P> {
+        match tokio::time::timeout(timeout, self.rx.recv()).await {
+            Ok(Some(msg)) => {
+                self.received += 1;
+                msg
+            }
+            Ok(None) => panic!("probe {:?}: the mailbox was closed", self.aid),
+            Err(_) => panic!("probe {:?}: no message within {timeout:?}", self.aid),
+        }
+    }
+
+    /// Waits for the next message and checks its payload.
+    pub async fn expect_payload(&mut self, expected: &P, timeout: Duration) -> Message<A, P>
+    where
+        P: PartialEq,
+    {
+        let msg = self.expect_msg(timeout).await;
+        assert_eq!(
+            msg.payload(),
+            expected,
+            "probe {:?}: unexpected payload",
+            self.aid
+        );
+        msg
+    }
+
+    /// Waits for the given duration, panics if a message arrives meanwhile.
+    pub async fn expect_no_msg(&mut self, duration: Duration) {
+        if let Ok(Some(msg)) = tokio::time::timeout(duration, self.rx.recv()).await {
+            panic!("probe {:?}: unexpected message {msg:?}", self.aid);
+        }
+    }
+
+    /// Sends the payload, on behalf of the probe, to the given actor.
+    pub async fn send<T>(&self, t: T, payload: P) -> Result<()>
+    where
+        T: Into<ToId<A>>,
+    {
+        self.forward(Message::new(self.aid.clone(), t, payload))
+            .await
+    }
+
+    /// Replies to the message, on behalf of the probe.
+    pub async fn reply(&self, msg: &Message<A, P>, payload: P) -> Result<()> {
+        let mut reply = Message::new(self.aid.clone(), msg.fid().as_ref().clone(), payload);
+        if let Some(mid) = msg.mid() {
+            reply = reply.with_cid(mid.clone());
+        }
+        self.forward(reply).await
+    }
+
+    async fn forward(&self, msg: Message<A, P>) -> Result<()> {
+        self.bus
+            .bus_tx()
+            .send(msg)
+            .await
+            .map_err(|_e| anyhow!("Failed to send the message to the bus"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Context;
+
+    const TIMEOUT: Duration = Duration::from_secs(3);
+
+    #[tokio::test(start_paused = true)]
+    async fn expect_msg() {
+        let mut kit = TestKit::<u8, u32>::spawn().await.unwrap();
+
+        // the doubler replies with twice the payload.
+        kit.spawn_actor(10, |msg: Message<u8, u32>, ctx: &mut Context<u8, u32>| {
+            ctx.reply(&msg, msg.payload() * 2);
+        })
+        .await
+        .unwrap();
+        let mut probe = kit.probe(1).await.unwrap();
+
+        kit.inject(Message::new(1, 10, 21)).await.unwrap();
+        let msg = probe.expect_payload(&42, TIMEOUT).await;
+        assert_eq!(msg.fid().as_ref(), &10);
+
+        probe.send(10, 2).await.unwrap();
+        probe.expect_payload(&4, TIMEOUT).await;
+        assert_eq!(probe.received(), 2);
+
+        probe.expect_no_msg(TIMEOUT).await;
+
+        let report = kit.stop(TIMEOUT).await.unwrap();
+        assert_eq!(report.stopped, vec![10]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    #[should_panic(expected = "no message within")]
+    async fn expect_msg_timeout() {
+        let mut kit = TestKit::<u8, u32>::spawn().await.unwrap();
+        let mut probe = kit.probe(1).await.unwrap();
+
+        probe.expect_msg(TIMEOUT).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    #[should_panic(expected = "unexpected message")]
+    async fn expect_no_msg_received() {
+        let mut kit = TestKit::<u8, u32>::spawn().await.unwrap();
+        let mut probe = kit.probe(1).await.unwrap();
+
+        kit.inject(Message::new(2, 1, 7)).await.unwrap();
+        probe.expect_no_msg(TIMEOUT).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timers_on_paused_clock() {
+        let mut kit = TestKit::<u8, u32>::spawn().await.unwrap();
+        let mut probe = kit.probe(1).await.unwrap();
+
+        let timer = kit
+            .bus()
+            .send_interval(Message::new(2, 1, 7), Duration::from_secs(60))
+            .unwrap();
+
+        kit.advance(Duration::from_secs(59)).await;
+        assert_eq!(probe.received(), 0);
+        probe.expect_no_msg(Duration::from_millis(900)).await;
+        probe.expect_payload(&7, Duration::from_millis(200)).await;
+        probe.expect_payload(&7, Duration::from_secs(61)).await;
+
+        timer.cancel();
+        probe.expect_no_msg(Duration::from_secs(600)).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ask_probe() {
+        let mut kit = TestKit::<u8, u32>::spawn().await.unwrap();
+        let mut probe = kit.probe(10).await.unwrap();
+
+        let bus = kit.bus().clone();
+        let ask = tokio::spawn(async move { bus.ask(Message::new(1, 10, 1), TIMEOUT).await });
+
+        let msg = probe.expect_msg(TIMEOUT).await;
+        probe.reply(&msg, 2).await.unwrap();
+
+        let reply = ask.await.unwrap().unwrap();
+        assert_eq!(reply.payload(), &2);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::Message;
+use act_identifiers::ActorId;
+use anyhow::{anyhow, Result};
+use std::{fmt::Debug, time::Duration};
+use tokio::{
+    sync::mpsc::Sender,
+    task::{AbortHandle, JoinHandle},
+    time::{interval_at, sleep_until, Instant, MissedTickBehavior},
+};
+
+/// The handle of a scheduled message. Dropping the handle does not
+/// cancel the timer, [`TimerHandle::cancel`] has to be called instead.
+/// The timers scheduled through a [`Context`](crate::Context) are
+/// cancelled as well when the actor stops.
+#[derive(Debug)]
+pub struct TimerHandle {
+    handle: JoinHandle<()>,
+}
+
+impl TimerHandle {
+    /// Cancels the timer. A message which was already sent is not recalled.
+    pub fn cancel(&self) {
+        self.handle.abort();
+    }
+
+    /// Returns `true` once the timer fired for the last time or was cancelled.
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    pub(crate) fn abort_handle(&self) -> AbortHandle {
+        self.handle.abort_handle()
+    }
+}
+
+/// Sends the message to the bus once the delay elapsed.
+pub(crate) fn send_after<A, P>(
+    bus_tx: Sender<Message<A, P>>,
+    msg: Message<A, P>,
+    delay: Duration,
+) -> TimerHandle
+where
+    A: ActorId + Debug + Send + 'static,
+    P: Send + 'static,
+{
+    // The delay counts from the call, not from when the task first runs.
+    let deadline = Instant::now() + delay;
+    let handle = tokio::spawn(async move {
+        sleep_until(deadline).await;
+        let _ = bus_tx.send(msg).await;
+    });
+    TimerHandle { handle }
+}
+
+/// Sends a new message, built by `make`, to the bus every period. The
+/// first message is sent after one period. A tick missed because the bus
+/// was busy delays the following ones, so messages never come in bursts.
+/// Fails if the period is zero.
+pub(crate) fn send_interval<A, P, F>(
+    bus_tx: Sender<Message<A, P>>,
+    period: Duration,
+    mut make: F,
+) -> Result<TimerHandle>
+where
+    A: ActorId + Debug + Send + 'static,
+    P: Send + 'static,
+    F: FnMut() -> Message<A, P> + Send + 'static,
+{
+    if period.is_zero() {
+        return Err(anyhow!("Failed to schedule a message every {period:?}"));
+    }
+    let start = Instant::now() + period;
+    let handle = tokio::spawn(async move {
+        let mut interval = interval_at(start, period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if bus_tx.send(make()).await.is_err() {
+                // The bus is gone, there is nobody to deliver the messages.
+                break;
+            }
+        }
+    });
+    Ok(TimerHandle { handle })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc::channel;
+
+    #[tokio::test(start_paused = true)]
+    async fn after() {
+        let (tx, mut rx) = channel::<Message<u8, u32>>(10);
+        let start = Instant::now();
+
+        let timer = send_after(tx, Message::new(1, 2, 7), Duration::from_secs(5));
+
+        let msg = rx.recv().await.unwrap();
+        assert_eq!(msg.payload(), &7);
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+
+        tokio::task::yield_now().await;
+        assert!(timer.is_finished());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancel_after() {
+        let (tx, mut rx) = channel::<Message<u8, u32>>(10);
+
+        let timer = send_after(tx, Message::new(1, 2, 7), Duration::from_secs(5));
+        timer.cancel();
+
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn interval() {
+        let (tx, mut rx) = channel::<Message<u8, u32>>(10);
+        let start = Instant::now();
+
+        let mut tick = 0;
+        let timer = send_interval(tx, Duration::from_secs(2), move || {
+            tick += 1;
+            Message::new(1, 2, tick)
+        })
+        .unwrap();
+
+        for i in 1..=3 {
+            let msg = rx.recv().await.unwrap();
+            assert_eq!(msg.payload(), &i);
+            assert_eq!(start.elapsed(), Duration::from_secs(2 * i as u64));
+        }
+
+        timer.cancel();
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn zero_interval() {
+        let (tx, _rx) = channel::<Message<u8, u32>>(10);
+        assert!(send_interval(tx, Duration::ZERO, || Message::new(1, 2, 0)).is_err());
+    }
+}
@@ -0,0 +1,384 @@
+use crate::{Message, Priority};
+use act_identifiers::{ActorId, FromId, MsgId, ToId};
+use act_ids::Identifier;
+use anyhow::{anyhow, Result};
+use std::fmt::Debug;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The largest frame a node accepts, larger frames close the connection.
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// The Wire trait converts a value to and from the bytes sent between
+/// nodes. Integers are big endian, strings and frames are prefixed by
+/// their length. The identifiers use their
+/// [`Binary`](act_identifiers::Binary) encoding.
+pub trait Wire: Sized {
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Decodes a value from the front of the buffer, advancing it.
+    fn decode(buf: &mut &[u8]) -> Result<Self>;
+}
+
+/// Takes the next `n` bytes from the front of the buffer.
+fn take<'a>(buf: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+    if buf.len() < n {
+        return Err(anyhow!(
+            "Failed to decode, {n} bytes needed but {} left",
+            buf.len()
+        ));
+    }
+    let (head, tail) = buf.split_at(n);
+    *buf = tail;
+    Ok(head)
+}
+
+macro_rules! wire_int {
+    ($($t:ty),*) => {
+        $(
+            impl Wire for $t {
+                fn encode(&self, buf: &mut Vec<u8>) {
+                    buf.extend_from_slice(&self.to_be_bytes());
+                }
+
+                fn decode(buf: &mut &[u8]) -> Result<Self> {
+                    let bytes = take(buf, std::mem::size_of::<$t>())?;
+                    Ok(<$t>::from_be_bytes(bytes.try_into()?))
+                }
+            }
+        )*
+    };
+}
+
+wire_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl Wire for bool {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        u8::from(*self).encode(buf);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        match u8::decode(buf)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => Err(anyhow!("Failed to decode a bool from [{b}]")),
+        }
+    }
+}
+
+impl Wire for () {
+    fn encode(&self, _buf: &mut Vec<u8>) {}
+
+    fn decode(_buf: &mut &[u8]) -> Result<Self> {
+        Ok(())
+    }
+}
+
+impl Wire for String {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (self.len() as u32).encode(buf);
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        let len = u32::decode(buf)? as usize;
+        let bytes = take(buf, len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|e| anyhow!("Failed to decode a string [{e}]"))
+    }
+}
+
+impl<T> Wire for Vec<T>
+where
+    T: Wire,
+{
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (self.len() as u32).encode(buf);
+        for item in self {
+            item.encode(buf);
+        }
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        let len = u32::decode(buf)? as usize;
+        // Do not trust the length to preallocate, the frame bounds the items.
+        let mut items = Vec::new();
+        for _ in 0..len {
+            items.push(T::decode(buf)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<T> Wire for Option<T>
+where
+    T: Wire,
+{
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Some(value) => {
+                true.encode(buf);
+                value.encode(buf);
+            }
+            None => false.encode(buf),
+        }
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        if bool::decode(buf)? {
+            Ok(Some(T::decode(buf)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+//
+// Identifiers
+//
+
+/// Decodes an identifier from the front of the buffer, advancing it.
+pub(crate) fn decode_id<T>(buf: &mut &[u8]) -> Result<T>
+where
+    T: act_identifiers::Binary,
+{
+    T::decode(buf).map_err(|e| anyhow!("Failed to decode an identifier [{e}]"))
+}
+
+impl<A> Wire for FromId<A>
+where
+    A: ActorId + act_identifiers::Binary,
+{
+    fn encode(&self, buf: &mut Vec<u8>) {
+        act_identifiers::Binary::encode(self, buf);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        decode_id(buf)
+    }
+}
+
+impl<A> Wire for ToId<A>
+where
+    A: ActorId + act_identifiers::Binary,
+{
+    fn encode(&self, buf: &mut Vec<u8>) {
+        act_identifiers::Binary::encode(self, buf);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        decode_id(buf)
+    }
+}
+
+impl<A, I> Wire for MsgId<A, I>
+where
+    A: ActorId + act_identifiers::Binary,
+    I: Identifier + act_identifiers::Binary,
+{
+    fn encode(&self, buf: &mut Vec<u8>) {
+        act_identifiers::Binary::encode(self, buf);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        decode_id(buf)
+    }
+}
+
+//
+// Message
+//
+
+impl Wire for Priority {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let b: u8 = match self {
+            Priority::Normal => 0,
+            Priority::High => 1,
+        };
+        b.encode(buf);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        match u8::decode(buf)? {
+            0 => Ok(Priority::Normal),
+            1 => Ok(Priority::High),
+            b => Err(anyhow!("Failed to decode a priority from [{b}]")),
+        }
+    }
+}
+
+impl<A, P> Wire for Message<A, P>
+where
+    A: ActorId + Clone + Debug + act_identifiers::Binary,
+    P: Wire,
+{
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.fid().encode(buf);
+        self.tid().encode(buf);
+        self.mid().cloned().encode(buf);
+        self.cid().cloned().encode(buf);
+        self.priority().encode(buf);
+        self.payload().encode(buf);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        let fid = FromId::<A>::decode(buf)?;
+        let tid = ToId::<A>::decode(buf)?;
+        let mid = Option::<MsgId<A, u64>>::decode(buf)?;
+        let cid = Option::<MsgId<A, u64>>::decode(buf)?;
+        let priority = Priority::decode(buf)?;
+        let payload = P::decode(buf)?;
+
+        let mut msg = Message::new(fid, tid, payload).with_priority(priority);
+        if let Some(mid) = mid {
+            msg = msg.with_mid(mid);
+        }
+        if let Some(cid) = cid {
+            msg = msg.with_cid(cid);
+        }
+        Ok(msg)
+    }
+}
+
+//
+// Frames
+//
+
+/// Encodes the value as a frame: its length followed by its bytes.
+pub(crate) fn encode_frame<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: Wire,
+{
+    let mut frame = vec![0; 4];
+    value.encode(&mut frame);
+
+    let len = frame.len() - 4;
+    if len > MAX_FRAME_LEN {
+        return Err(anyhow!("Failed to encode a frame of {len} bytes"));
+    }
+    frame[..4].copy_from_slice(&(len as u32).to_be_bytes());
+    Ok(frame)
+}
+
+/// Reads the next frame and decodes its value. Returns `None` when the
+/// connection was closed between two frames.
+pub(crate) async fn read_frame<R, T>(reader: &mut R) -> Result<Option<T>>
+where
+    R: AsyncRead + Unpin,
+    T: Wire,
+{
+    let len = match reader.read_u32().await {
+        Ok(len) => len as usize,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(anyhow!("Failed to read the frame length [{e}]")),
+    };
+    if len > MAX_FRAME_LEN {
+        return Err(anyhow!("Failed to read a frame of {len} bytes"));
+    }
+
+    let mut frame = vec![0; len];
+    reader
+        .read_exact(&mut frame)
+        .await
+        .map_err(|e| anyhow!("Failed to read the frame [{e}]"))?;
+
+    let mut buf = frame.as_slice();
+    let value = T::decode(&mut buf)?;
+    if !buf.is_empty() {
+        return Err(anyhow!(
+            "Failed to decode the frame, {} bytes left",
+            buf.len()
+        ));
+    }
+    Ok(Some(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    fn round_trip<T: Wire>(value: &T) -> T {
+        let mut buf = Vec::new();
+        value.encode(&mut buf);
+        let mut slice = buf.as_slice();
+        let decoded = T::decode(&mut slice).unwrap();
+        assert!(slice.is_empty());
+        decoded
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(round_trip(&7_u8), 7);
+        assert_eq!(round_trip(&-7_i32), -7);
+        assert_eq!(round_trip(&u64::MAX), u64::MAX);
+
+        let mut buf = Vec::new();
+        0x0102_u16.encode(&mut buf);
+        assert_eq!(buf, vec![1, 2]);
+    }
+
+    #[test]
+    fn strings_and_options() {
+        assert_eq!(round_trip(&"hello".to_string()), "hello");
+        assert_eq!(round_trip(&Some(3_u32)), Some(3));
+        assert_eq!(round_trip(&None::<u32>), None);
+        assert_eq!(round_trip(&vec![1_u16, 2, 3]), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn truncated() {
+        let mut buf = Vec::new();
+        "hello".to_string().encode(&mut buf);
+        buf.pop();
+        assert!(String::decode(&mut buf.as_slice()).is_err());
+        assert!(bool::decode(&mut [2_u8].as_slice()).is_err());
+    }
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(round_trip(&FromId::new(1_u32)), FromId::new(1));
+        assert_eq!(round_trip(&ToId::new(2_u32)), ToId::new(2));
+        assert_eq!(
+            round_trip(&MsgId::from((3_u32, 4_u64))),
+            MsgId::from((3, 4))
+        );
+
+        // the identifiers are encoded as by Binary.
+        let mid = MsgId::from((300_u32, 4_u64));
+        let mut buf = Vec::new();
+        Wire::encode(&mid, &mut buf);
+        assert_eq!(buf, act_identifiers::Binary::to_bytes(&mid));
+    }
+
+    #[test]
+    fn message() {
+        let msg = Message::new(1_u32, 2_u32, "payload".to_string())
+            .with_mid(MsgId::from((1, 10)))
+            .with_priority(Priority::High);
+
+        let decoded = round_trip(&msg);
+        assert_eq!(decoded.fid().as_ref(), &1);
+        assert_eq!(decoded.tid().as_ref(), &2);
+        assert_eq!(decoded.mid(), Some(&MsgId::from((1, 10))));
+        assert!(decoded.cid().is_none());
+        assert_eq!(decoded.priority(), Priority::High);
+        assert_eq!(decoded.payload(), "payload");
+    }
+
+    #[tokio::test]
+    async fn frames() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+
+        for value in ["one", "two"] {
+            let frame = encode_frame(&value.to_string()).unwrap();
+            client.write_all(&frame).await.unwrap();
+        }
+        drop(client);
+
+        let one: Option<String> = read_frame(&mut server).await.unwrap();
+        let two: Option<String> = read_frame(&mut server).await.unwrap();
+        let end: Option<String> = read_frame(&mut server).await.unwrap();
+        assert_eq!(one.as_deref(), Some("one"));
+        assert_eq!(two.as_deref(), Some("two"));
+        assert!(end.is_none());
+    }
+}
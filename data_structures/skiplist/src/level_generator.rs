@@ -0,0 +1,53 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+/// Draws the levels of the new nodes of a skiplist: a node reaches the
+/// level `n + 1` with the probability `p` once it reaches the level `n`,
+/// so about one node in `1 / p^n` reaches the level `n`.
+#[derive(Debug, Clone)]
+pub struct LevelGenerator {
+    total: usize,
+    p: f64,
+    /// The state of a xorshift generator, never zero.
+    state: u64,
+}
+
+impl LevelGenerator {
+    /// Panics if there are no levels or if `p` is not within `(0, 1)`.
+    pub fn new(total: usize, p: f64) -> Self {
+        assert!(total > 0, "a skiplist needs at least one level");
+        assert!(p > 0.0 && p < 1.0, "the probability must be within (0, 1)");
+        Self {
+            total,
+            p,
+            // The hasher keys are random, so is the seed.
+            state: RandomState::new().build_hasher().finish() | 1,
+        }
+    }
+
+    /// The number of levels, the generated ones being below.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// A random level within `0..total`.
+    pub fn random(&mut self) -> usize {
+        let mut level = 0;
+        while level + 1 < self.total && self.next_f64() < self.p {
+            level += 1;
+        }
+        level
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        // The 53 high bits of the xorshift* output, within [0, 1).
+        (x.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
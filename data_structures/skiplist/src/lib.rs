@@ -1 +1,5 @@
+pub mod level_generator;
 pub mod node;
+pub mod skiplist;
+
+pub use skiplist::{IntoIter, Iter, SkipList};
@@ -1,3 +1,8 @@
+use skiplist_lib::SkipList;
+
 fn main() {
-    println!("Hello, world!");
+    let mut list = [5, 3, 8, 1].into_iter().collect::<SkipList<_>>();
+    list.insert(4);
+    list.remove(&8);
+    println!("{list}");
 }
@@ -17,7 +17,9 @@ pub struct Node<T> {
     // *must* be of length `self.level + 1`.  links[0] stores a pointer to the
     // next node, which will have to be dropped.
     pub links: Vec<Link<T>>,
-    // The corresponding length of each link
+    // The corresponding length of each link: how many nodes ahead the linked
+    // node is. A missing link reaches past the last node, its length is the
+    // number of nodes after this one.
     pub links_len: Vec<usize>,
 }
 
@@ -41,8 +43,8 @@ impl<T> Node<T> {
             item: None,
             level: total_levels - 1,
             prev: None,
-            links: iter::repeat(None).take(total_levels).collect(),
-            links_len: iter::repeat(0).take(total_levels).collect(),
+            links: iter::repeat_n(None, total_levels).collect(),
+            links_len: iter::repeat_n(0, total_levels).collect(),
         }
     }
 
@@ -52,8 +54,8 @@ impl<T> Node<T> {
             item: Some(item),
             level,
             prev: None,
-            links: iter::repeat(None).take(level + 1).collect(),
-            links_len: iter::repeat(0).take(level + 1).collect(),
+            links: iter::repeat_n(None, level + 1).collect(),
+            links_len: iter::repeat_n(0, level + 1).collect(),
         }
     }
 
@@ -81,7 +83,9 @@ impl<T> Node<T> {
 
     /// Takes the next node and set next_node.prev as null.
     ///
-    /// SAFETY: please make sure no link at level 1 or greater becomes dangling.
+    /// # Safety
+    ///
+    /// Please make sure no link at level 1 or greater becomes dangling.
     pub unsafe fn take_tail(&mut self) -> Option<Box<Self>> {
         self.links[0].take().map(|p| {
             let mut next = Box::from_raw(p.as_ptr());
@@ -94,7 +98,9 @@ impl<T> Node<T> {
     /// Replace the next node.
     /// Return the old node.
     ///
-    /// SAFETY: please makes sure all links are fixed.
+    /// # Safety
+    ///
+    /// Please makes sure all links are fixed.
     pub unsafe fn replace_tail(&mut self, mut new_next: Box<Self>) -> Option<Box<Self>> {
         let mut old_next = self.take_tail();
         if let Some(old_next) = old_next.as_mut() {
@@ -128,7 +134,7 @@ impl<T> Node<T> {
         let mut current_node = self as *mut Self;
         // `level_heads` records every head of the linked list.
         // A head is the last node of a given level that is not after current_node.
-        let mut level_heads: Vec<_> = iter::repeat(current_node).take(self.level + 1).collect();
+        let mut level_heads: Vec<_> = iter::repeat_n(current_node, self.level + 1).collect();
         // SAFETY: a huge block of pointer manipulation.
         unsafe {
             while let Some(mut next_node) = (*current_node).take_tail() {
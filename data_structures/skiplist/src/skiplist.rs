@@ -0,0 +1,748 @@
+use crate::{level_generator::LevelGenerator, node::Node};
+use core::fmt;
+use std::{iter::FusedIterator, marker::PhantomData, ptr::NonNull};
+
+/// The number of levels of a skiplist built with [`SkipList::new`], enough
+/// for about 65536 items.
+const DEFAULT_LEVELS: usize = 16;
+
+/// The probability for a node to reach the next level.
+const LEVEL_PROBABILITY: f64 = 0.5;
+
+/// For every level, the last node before the searched position and its
+/// position, the head being at the position 0.
+type Update<T> = Vec<(*mut Node<T>, usize)>;
+
+/// An ordered collection backed by a skiplist: the items are kept sorted,
+/// an item equal to others being placed after them, and the insertions,
+/// removals and lookups take `O(log n)` on average.
+///
+/// The lengths of the links give the positions of the nodes, so the items
+/// can be accessed and removed by index and ranked in `O(log n)` too.
+pub struct SkipList<T> {
+    head: Box<Node<T>>,
+    len: usize,
+    level_generator: LevelGenerator,
+}
+
+// SAFETY: the skiplist owns all its nodes, like a `Vec<T>` would.
+unsafe impl<T: Send> Send for SkipList<T> {}
+unsafe impl<T: Sync> Sync for SkipList<T> {}
+
+impl<T> SkipList<T> {
+    pub fn new() -> Self {
+        Self::with_levels(DEFAULT_LEVELS)
+    }
+
+    /// Sizes the levels of the skiplist for about `capacity` items. The
+    /// skiplist may hold more items, its operations getting slower.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let levels = capacity.max(2).next_power_of_two().trailing_zeros() as usize;
+        Self::with_levels(levels)
+    }
+
+    fn with_levels(levels: usize) -> Self {
+        Self {
+            head: Box::new(Node::head(levels)),
+            len: 0,
+            level_generator: LevelGenerator::new(levels, LEVEL_PROBABILITY),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes all the items.
+    pub fn clear(&mut self) {
+        // SAFETY: all the nodes are dropped, one at a time so a long list does
+        // not overflow the stack, and the head links are reset afterwards.
+        let mut next = unsafe { self.head.take_tail() };
+        while let Some(mut node) = next {
+            next = unsafe { node.take_tail() };
+        }
+        self.head.links.fill(None);
+        self.head.links_len.fill(0);
+        self.len = 0;
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.head.next_ref().and_then(|node| node.item.as_ref())
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.walk(|_, _| true).0.item.as_ref()
+    }
+
+    /// Removes and returns the first item.
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.remove_at(0)
+    }
+
+    /// Removes and returns the last item.
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.len
+            .checked_sub(1)
+            .and_then(|index| self.remove_at(index))
+    }
+
+    /// The item at the given index, in `O(log n)`.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+        // The item at the index is at the position index + 1, after the head.
+        let (node, _) = self.walk(|_, position| position <= index + 1);
+        node.item.as_ref()
+    }
+
+    /// Removes and returns the item at the given index, in `O(log n)`.
+    pub fn remove_at(&mut self, index: usize) -> Option<T> {
+        if index >= self.len {
+            return None;
+        }
+        let update = self.walk_mut(|_, position| position <= index);
+        Some(self.unlink_next(&update))
+    }
+
+    /// Splits the skiplist in two at the given index, the second one
+    /// starting with the item at the index, in `O(log n)`.
+    ///
+    /// Panics if the index is above the length.
+    pub fn split_at(mut self, index: usize) -> (SkipList<T>, SkipList<T>) {
+        assert!(index <= self.len, "the index is above the length");
+        let mut tail = Self::with_levels(self.head.level + 1);
+        tail.len = self.len - index;
+
+        let update = self.walk_mut(|_, position| position <= index);
+        // SAFETY: the pointers of `update` come from `walk_mut`, and every
+        // link crossing the index moves from its node to the new head.
+        unsafe {
+            for (level, &(prev, prev_position)) in update.iter().enumerate().skip(1) {
+                let prev = &mut *prev;
+                tail.head.links[level] = prev.links[level].take();
+                tail.head.links_len[level] = prev_position + prev.links_len[level] - index;
+                prev.links_len[level] = index - prev_position;
+            }
+            if let Some(first) = (*{ update[0].0 }).take_tail() {
+                tail.head.replace_tail(first);
+            }
+        }
+        self.len = index;
+        (self, tail)
+    }
+
+    /// Keeps only the items for which `pred` returns `true`, in order.
+    pub fn retain<F>(&mut self, mut pred: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let removed = self.head.retain(|_, next| pred(next));
+        self.len -= removed;
+    }
+
+    /// An iterator over the items, in order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            front: self.head.links[0],
+            back: self.last_node(),
+            len: self.len,
+            marker: PhantomData,
+        }
+    }
+
+    fn last_node(&self) -> Option<NonNull<Node<T>>> {
+        let (node, position) = self.walk(|_, _| true);
+        (position > 0).then(|| NonNull::from(node))
+    }
+
+    /// Goes down the levels from the head, moving right as long as `go_right`
+    /// accepts the next item and its position. Returns the last node reached
+    /// and its position.
+    fn walk<F>(&self, mut go_right: F) -> (&Node<T>, usize)
+    where
+        F: FnMut(&T, usize) -> bool,
+    {
+        let mut node: &Node<T> = &self.head;
+        let mut position = 0;
+        for level in (0..=self.head.level).rev() {
+            // SAFETY: the links point to nodes owned by the skiplist.
+            while let Some(next) = node.links[level].map(|p| unsafe { &*p.as_ptr() }) {
+                let next_position = position + node.links_len[level];
+                if !go_right(next.item.as_ref().unwrap(), next_position) {
+                    break;
+                }
+                node = next;
+                position = next_position;
+            }
+        }
+        (node, position)
+    }
+
+    /// Same as [`walk`](Self::walk), but records the last node reached at
+    /// every level, so the links around the next node can be rewired.
+    fn walk_mut<F>(&mut self, mut go_right: F) -> Update<T>
+    where
+        F: FnMut(&T, usize) -> bool,
+    {
+        let levels = self.head.level + 1;
+        let mut update = vec![(std::ptr::null_mut(), 0); levels];
+        let mut node = self.head.as_mut() as *mut Node<T>;
+        let mut position = 0;
+        // SAFETY: the links point to nodes owned by the skiplist, which is
+        // borrowed mutably while the pointers are used.
+        unsafe {
+            for level in (0..levels).rev() {
+                while let Some(next) = (&(*node).links)[level] {
+                    let next_position = position + (&(*node).links_len)[level];
+                    let item = (*next.as_ptr()).item.as_ref().unwrap();
+                    if !go_right(item, next_position) {
+                        break;
+                    }
+                    node = next.as_ptr();
+                    position = next_position;
+                }
+                update[level] = (node, position);
+            }
+        }
+        update
+    }
+
+    /// Links the node after `update[0]`, the nodes of `update` being the last
+    /// ones before it at every level.
+    fn link_next(&mut self, update: &Update<T>, node: Box<Node<T>>) {
+        let level = node.level;
+        let position = update[0].1 + 1;
+        let node = NonNull::from(Box::leak(node));
+        // SAFETY: the pointers of `update` come from `walk_mut` and the new
+        // node is linked at all its levels before this returns.
+        unsafe {
+            let new = &mut *node.as_ptr();
+            for (l, &(prev, prev_position)) in update.iter().enumerate() {
+                let prev = &mut *prev;
+                if l <= level {
+                    // The node is between prev and the node prev links to,
+                    // which moves one position further.
+                    new.links[l] = prev.links[l];
+                    new.links_len[l] = prev_position + prev.links_len[l] + 1 - position;
+                    prev.links[l] = Some(node);
+                    prev.links_len[l] = position - prev_position;
+                } else {
+                    prev.links_len[l] += 1;
+                }
+            }
+            new.prev = NonNull::new(update[0].0);
+            if let Some(next) = new.links[0] {
+                (*next.as_ptr()).prev = Some(node);
+            }
+        }
+        self.len += 1;
+    }
+
+    /// Unlinks the node after `update[0]` and returns its item, the nodes of
+    /// `update` being the last ones before it at every level.
+    fn unlink_next(&mut self, update: &Update<T>) -> T {
+        // SAFETY: the pointers of `update` come from `walk_mut`, the node
+        // after `update[0]` exists and all the links to it are replaced.
+        let mut node = unsafe {
+            let target = (&(*update[0].0).links)[0].unwrap().as_ptr();
+            // Fixes links above level 0, like `Node::retain`.
+            for (level, &(prev, _)) in update.iter().enumerate().skip(1) {
+                let prev = &mut *prev;
+                if prev.links[level].is_some_and(|p| p.as_ptr() == target) {
+                    let removed = &*target;
+                    prev.links_len[level] += removed.links_len[level];
+                    prev.links_len[level] -= 1;
+                    prev.links[level] = removed.links[level];
+                } else {
+                    prev.links_len[level] -= 1;
+                }
+            }
+            // Fix the link at level 0.
+            let prev = &mut *{ update[0].0 };
+            let mut node = prev.take_tail().unwrap();
+            if let Some(next) = node.take_tail() {
+                prev.replace_tail(next);
+            }
+            node
+        };
+        self.len -= 1;
+        node.item.take().unwrap()
+    }
+}
+
+impl<T> SkipList<T>
+where
+    T: Ord,
+{
+    /// Inserts the item after the equal ones.
+    pub fn insert(&mut self, item: T) {
+        let update = self.walk_mut(|next, _| *next <= item);
+        let level = self.level_generator.random();
+        self.link_next(&update, Box::new(Node::new(item, level)));
+    }
+
+    /// Removes the first item equal to the given one.
+    pub fn remove(&mut self, item: &T) -> Option<T> {
+        let update = self.walk_mut(|next, _| next < item);
+        // SAFETY: the pointer comes from `walk_mut`.
+        let found = unsafe { (*update[0].0).next_ref() }
+            .is_some_and(|next| next.item.as_ref() == Some(item));
+        found.then(|| self.unlink_next(&update))
+    }
+
+    /// The index of the first item equal to the given one, in `O(log n)`.
+    pub fn rank_of(&self, item: &T) -> Option<usize> {
+        let (node, position) = self.walk(|next, _| next < item);
+        // The next item is at the index position.
+        node.next_ref()
+            .is_some_and(|next| next.item.as_ref() == Some(item))
+            .then_some(position)
+    }
+
+    pub fn contains(&self, item: &T) -> bool {
+        let (node, _) = self.walk(|next, _| next < item);
+        node.next_ref()
+            .is_some_and(|next| next.item.as_ref() == Some(item))
+    }
+}
+
+impl<T> Default for SkipList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for SkipList<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T> Clone for SkipList<T>
+where
+    T: Ord + Clone,
+{
+    fn clone(&self) -> Self {
+        let mut list = Self::with_levels(self.head.level + 1);
+        list.extend(self.iter().cloned());
+        list
+    }
+}
+
+impl<T> fmt::Debug for SkipList<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T> fmt::Display for SkipList<T>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, item) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", item)?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl<T> Extend<T> for SkipList<T>
+where
+    T: Ord,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(item);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for SkipList<T>
+where
+    T: Ord,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = Self::new();
+        list.extend(iter);
+        list
+    }
+}
+
+/// An iterator over the items of a skiplist, in order.
+pub struct Iter<'a, T> {
+    front: Option<NonNull<Node<T>>>,
+    back: Option<NonNull<Node<T>>>,
+    /// The number of items between front and back, both included.
+    len: usize,
+    marker: PhantomData<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        // SAFETY: the node is owned by the skiplist, borrowed for 'a.
+        let node = unsafe { &*self.front?.as_ptr() };
+        self.front = node.links[0];
+        self.len -= 1;
+        node.item.as_ref()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        // SAFETY: the node is owned by the skiplist, borrowed for 'a.
+        let node = unsafe { &*self.back?.as_ptr() };
+        self.back = node.prev;
+        self.len -= 1;
+        node.item.as_ref()
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+impl<T> FusedIterator for Iter<'_, T> {}
+
+impl<T> Clone for Iter<'_, T> {
+    fn clone(&self) -> Self {
+        Self { ..*self }
+    }
+}
+
+/// An iterator moving the items out of a skiplist, in order.
+pub struct IntoIter<T> {
+    list: SkipList<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.list.pop_first()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.list.pop_last()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+impl<T> FusedIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for SkipList<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { list: self }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a SkipList<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::{cell::Cell, collections::HashMap, rc::Rc};
+
+    /// Checks the order of the items, the back links and the lengths of
+    /// the links at every level.
+    pub(crate) fn check<T: Ord>(list: &SkipList<T>) {
+        let mut positions = HashMap::new();
+        positions.insert(&*list.head as *const Node<T>, 0);
+
+        let mut node: &Node<T> = &list.head;
+        let mut position = 0;
+        while let Some(next) = node.next_ref() {
+            position += 1;
+            assert!(std::ptr::eq(next.prev.unwrap().as_ptr(), node));
+            if let (Some(a), Some(b)) = (node.item.as_ref(), next.item.as_ref()) {
+                assert!(a <= b);
+            }
+            positions.insert(next as *const Node<T>, position);
+            node = next;
+        }
+        assert_eq!(position, list.len());
+
+        for level in 0..=list.head.level {
+            let mut node: &Node<T> = &list.head;
+            loop {
+                let from = positions[&(node as *const Node<T>)];
+                match node.links[level] {
+                    Some(next) => {
+                        let to = positions[&(next.as_ptr() as *const Node<T>)];
+                        assert_eq!(node.links_len[level], to - from);
+                        node = unsafe { &*next.as_ptr() };
+                    }
+                    None => {
+                        assert_eq!(node.links_len[level], list.len() - from);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn insert_and_contains() {
+        let mut list = SkipList::new();
+        assert!(list.is_empty());
+        assert_eq!(list.first(), None);
+        assert_eq!(list.last(), None);
+
+        for i in [5, 1, 4, 2, 3, 3] {
+            list.insert(i);
+            check(&list);
+        }
+        assert_eq!(list.len(), 6);
+        assert_eq!(list.first(), Some(&1));
+        assert_eq!(list.last(), Some(&5));
+        assert!(list.contains(&3));
+        assert!(!list.contains(&6));
+        assert!(!list.contains(&0));
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), [1, 2, 3, 3, 4, 5]);
+    }
+
+    #[test]
+    fn remove() {
+        let mut list = (0..100).rev().collect::<SkipList<_>>();
+        check(&list);
+        for i in (0..100).step_by(3) {
+            assert_eq!(list.remove(&i), Some(i));
+            check(&list);
+        }
+        assert_eq!(list.remove(&0), None);
+        assert_eq!(list.len(), 66);
+        assert!(list.iter().all(|i| i % 3 != 0));
+
+        assert_eq!(list.pop_first(), Some(1));
+        assert_eq!(list.pop_last(), Some(98));
+        check(&list);
+        assert_eq!(list.len(), 64);
+    }
+
+    #[test]
+    fn duplicates_keep_insertion_order() {
+        // Ordered by the key only.
+        #[derive(Debug)]
+        struct Entry(u8, &'static str);
+        impl PartialEq for Entry {
+            fn eq(&self, other: &Self) -> bool {
+                self.0 == other.0
+            }
+        }
+        impl Eq for Entry {}
+        impl PartialOrd for Entry {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Entry {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                self.0.cmp(&other.0)
+            }
+        }
+
+        let mut list = SkipList::new();
+        list.insert(Entry(1, "a"));
+        list.insert(Entry(0, "b"));
+        list.insert(Entry(1, "c"));
+        let names = list.iter().map(|e| e.1).collect::<Vec<_>>();
+        assert_eq!(names, ["b", "a", "c"]);
+        assert_eq!(list.remove(&Entry(1, "")).unwrap().1, "a");
+    }
+
+    #[test]
+    fn iterators() {
+        let list = [3, 1, 2].into_iter().collect::<SkipList<_>>();
+        assert_eq!(list.iter().rev().collect::<Vec<_>>(), [&3, &2, &1]);
+
+        let mut iter = list.iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next_back(), Some(&3));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+
+        assert_eq!((&list).into_iter().count(), 3);
+        assert_eq!(format!("{list:?}"), "[1, 2, 3]");
+        assert_eq!(list.to_string(), "[1, 2, 3]");
+
+        let mut iter = list.clone().into_iter();
+        assert_eq!(iter.next_back(), Some(3));
+        assert_eq!(iter.collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(list.len(), 3);
+    }
+
+    #[test]
+    fn retain() {
+        let mut list = (0..50).collect::<SkipList<_>>();
+        list.retain(|i| i % 2 == 0);
+        check(&list);
+        assert_eq!(list.len(), 25);
+        list.insert(7);
+        check(&list);
+        assert!(list.contains(&7));
+    }
+
+    #[test]
+    fn drop_frees_all_items() {
+        #[derive(PartialEq, Eq, PartialOrd, Ord)]
+        struct Counted(u32, Rc<Cell<u32>>);
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.1.set(self.1.get() + 1);
+            }
+        }
+
+        let dropped = Rc::new(Cell::new(0));
+        let mut list = SkipList::new();
+        for i in 0..1000 {
+            list.insert(Counted(i, dropped.clone()));
+        }
+        list.pop_first();
+        assert_eq!(dropped.get(), 1);
+        drop(list);
+        assert_eq!(dropped.get(), 1000);
+    }
+
+    #[test]
+    fn clear_and_reuse() {
+        let mut list = SkipList::with_capacity(8);
+        list.extend(0..100);
+        check(&list);
+        list.clear();
+        assert!(list.is_empty());
+        check(&list);
+        list.insert(1);
+        assert_eq!(list.iter().collect::<Vec<_>>(), [&1]);
+    }
+
+    #[test]
+    fn long_list() {
+        let mut list = SkipList::new();
+        list.extend((0..100_000).map(|i| (i * 7919) % 100_000));
+        check(&list);
+        assert!(list.iter().copied().eq(0..100_000));
+    }
+
+    #[test]
+    fn get_and_rank() {
+        let list = (0..1000).map(|i| i * 2).collect::<SkipList<_>>();
+        for i in 0..1000 {
+            assert_eq!(list.get(i), Some(&(i * 2)));
+            assert_eq!(list.rank_of(&(i * 2)), Some(i));
+            assert_eq!(list.rank_of(&(i * 2 + 1)), None);
+        }
+        assert_eq!(list.get(1000), None);
+
+        let list = [1, 2, 2, 2, 3].into_iter().collect::<SkipList<_>>();
+        assert_eq!(list.rank_of(&2), Some(1));
+        assert_eq!(list.rank_of(&3), Some(4));
+    }
+
+    #[test]
+    fn remove_at() {
+        let mut list = (0..100).collect::<SkipList<_>>();
+        let mut expected = (0..100).collect::<Vec<_>>();
+        for index in [99, 0, 50, 17, 17, 60] {
+            assert_eq!(list.remove_at(index), Some(expected.remove(index)));
+            check(&list);
+        }
+        assert!(list.iter().copied().eq(expected.iter().copied()));
+        assert_eq!(list.remove_at(list.len()), None);
+    }
+
+    #[test]
+    fn split_at() {
+        for index in [0, 1, 37, 99, 100] {
+            let list = (0..100).collect::<SkipList<_>>();
+            let (mut head, mut tail) = list.split_at(index);
+            check(&head);
+            check(&tail);
+            assert!(head.iter().copied().eq(0..index));
+            assert!(tail.iter().copied().eq(index..100));
+
+            // both halves stay usable.
+            head.insert(1000);
+            tail.insert(0);
+            check(&head);
+            check(&tail);
+            assert_eq!(tail.get(0), Some(&0));
+            assert_eq!(head.last(), Some(&1000));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "above the length")]
+    fn split_at_out_of_bounds() {
+        let list = (0..3).collect::<SkipList<_>>();
+        let _ = list.split_at(4);
+    }
+
+    #[test]
+    fn order_statistics() {
+        // A leaderboard: the ranks follow the insertions and removals.
+        let mut list = SkipList::new();
+        let mut expected = Vec::new();
+        let mut x = 12345_u64;
+        for _ in 0..2000 {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let score = (x >> 33) % 500;
+            if score.is_multiple_of(5) && !expected.is_empty() {
+                let index = (x >> 20) as usize % expected.len();
+                assert_eq!(list.remove_at(index), Some(expected.remove(index)));
+            } else {
+                list.insert(score);
+                let index = expected.partition_point(|s| *s <= score);
+                expected.insert(index, score);
+            }
+        }
+        check(&list);
+        for (index, score) in expected.iter().enumerate() {
+            assert_eq!(list.get(index), Some(score));
+            assert_eq!(
+                list.rank_of(score),
+                Some(expected.partition_point(|s| s < score))
+            );
+        }
+    }
+}